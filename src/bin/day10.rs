@@ -1,102 +1,358 @@
-use std::iter::successors;
+use std::fmt::{self, Display};
 
-use aoc2023::read_grid;
+use aoc2023::{polygon::Polygon, read_grid};
 use simple_grid::{Grid, GridIndex};
 
 fn main() {
     let input = std::fs::read_to_string("inputs/day10").unwrap();
     let grid = read_grid(&input);
-    println!("Part 1: {}", path(&grid).len() / 2);
-    println!("Part 2: {}", enclosed_area(&grid));
+    match analyse(&grid) {
+        Ok(l) if std::env::args().nth(1).as_deref() == Some("--labels") => {
+            print!("{}", render(&grid, &classify(&grid, &l)));
+        }
+        Ok(l) => {
+            println!("Part 1: {}", l.cells.len() / 2);
+            println!("Part 2: {}", enclosed_area(&l.cells));
+        }
+        Err(e) => {
+            println!("{e}");
+            std::process::exit(1);
+        }
+    }
 }
 
-fn path(grid: &Grid<u8>) -> Vec<GridIndex> {
-    successors(Some(path_start(grid)), |(prev, curr)| {
-        step(grid, *prev, *curr)
-    })
-    .map(|(x, _)| x)
-    .collect::<Vec<_>>()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dir {
+    North,
+    East,
+    South,
+    West,
 }
 
-fn path_start(grid: &Grid<u8>) -> (GridIndex, GridIndex) {
-    let start = grid.position(|&c| c == b'S').unwrap();
-    let next = [
-        start
-            .up()
-            .and_then(|i| b"|F7".contains(&grid[i]).then_some(i)),
-        start
-            .right()
-            .and_then(|i| b"-7J".contains(&grid[i]).then_some(i)),
-        start
-            .down()
-            .and_then(|i| b"JL|".contains(&grid[i]).then_some(i)),
-        start
-            .left()
-            .and_then(|i| b"FL-".contains(&grid[i]).then_some(i)),
-    ]
-    .iter()
-    .find_map(|opt| *opt)
-    .unwrap();
-    (start, next)
+impl Dir {
+    const ALL: [Dir; 4] = [Dir::North, Dir::East, Dir::South, Dir::West];
+
+    fn opposite(self) -> Self {
+        match self {
+            Dir::North => Dir::South,
+            Dir::East => Dir::West,
+            Dir::South => Dir::North,
+            Dir::West => Dir::East,
+        }
+    }
+
+    fn step(self, grid: &Grid<u8>, i: GridIndex) -> Option<GridIndex> {
+        match self {
+            Dir::North => grid.up_index(i),
+            Dir::East => grid.right_index(i),
+            Dir::South => grid.down_index(i),
+            Dir::West => grid.left_index(i),
+        }
+    }
 }
 
-// Based on: https://en.wikipedia.org/wiki/Shoelace_formula
-fn enclosed_area(grid: &Grid<u8>) -> i32 {
-    let p = path(grid);
-    let n = p.len();
-    let xs: Vec<_> = p.iter().map(|i| i.column() as i32).collect();
-    let ys: Vec<_> = p.iter().map(|i| i.row() as i32).collect();
-    let products = (0..n).map(|i| xs[i] * (ys[(i + 1) % n] - ys[(i + n) % n]));
-    // because pipes are logically in-between cells, we need to correct the area by (n/2)-1
-    products.sum::<i32>().abs() - (n as i32 / 2) + 1
+/// The two directions a pipe connects, or `None` for anything that isn't a pipe.
+fn connections(tile: u8) -> Option<[Dir; 2]> {
+    match tile {
+        b'|' => Some([Dir::North, Dir::South]),
+        b'-' => Some([Dir::East, Dir::West]),
+        b'L' => Some([Dir::North, Dir::East]),
+        b'J' => Some([Dir::North, Dir::West]),
+        b'7' => Some([Dir::South, Dir::West]),
+        b'F' => Some([Dir::East, Dir::South]),
+        _ => None,
+    }
 }
 
-fn step(grid: &Grid<u8>, prev: GridIndex, curr: GridIndex) -> Option<(GridIndex, GridIndex)> {
-    match grid[curr] {
-        b'S' => None,
-        b'-' => Some((
-            curr,
-            if prev.column() < curr.column() { curr.right()? } else { curr.left()? },
-        )),
-        b'|' => Some((
-            curr,
-            if prev.row() < curr.row() { curr.down()? } else { curr.up()? },
-        )),
-        b'L' => Some((
-            curr,
-            if prev.row() != curr.row() { curr.right()? } else { curr.up()? },
-        )),
-        b'J' => Some((
-            curr,
-            if prev.row() != curr.row() { curr.left()? } else { curr.up()? },
-        )),
-        b'7' => Some((
-            curr,
-            if prev.row() != curr.row() { curr.left()? } else { curr.down()? },
-        )),
-        b'F' => Some((
-            curr,
-            if prev.row() != curr.row() { curr.right()? } else { curr.down()? },
+/// The pipe connecting both directions.
+fn pipe(a: Dir, b: Dir) -> Option<u8> {
+    b"|-LJ7F"
+        .iter()
+        .copied()
+        .find(|&tile| connections(tile).is_some_and(|c| c == [a, b] || c == [b, a]))
+}
+
+/// The closed loop of pipes through the start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Loop {
+    /// The pipe hidden under `S`
+    start_tile: u8,
+    /// The loop's tiles in order, starting with `S`
+    cells: Vec<GridIndex>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Problem {
+    MissingStart,
+    MultipleStarts(Vec<GridIndex>),
+    /// Fewer than two neighbours connect to the start
+    DeadEnd(Vec<Dir>),
+    /// Following the pipes, `from` leads `towards` a tile that doesn't connect back
+    Dangling {
+        from: GridIndex,
+        towards: Dir,
+    },
+    /// More than one choice of start tile closes a loop
+    Ambiguous(Vec<u8>),
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let at = |i: &GridIndex| format!("({}, {})", i.row() + 1, i.column() + 1);
+        match self {
+            Problem::MissingStart => write!(f, "no start tile"),
+            Problem::MultipleStarts(starts) => {
+                let starts: Vec<_> = starts.iter().map(at).collect();
+                write!(f, "multiple start tiles at {}", starts.join(", "))
+            }
+            Problem::DeadEnd(dirs) => write!(f, "the start only connects to {dirs:?}"),
+            Problem::Dangling { from, towards } => {
+                write!(f, "the pipe at {} leads {towards:?} into nothing", at(from))
+            }
+            Problem::Ambiguous(tiles) => write!(
+                f,
+                "the start could be any of {}",
+                String::from_utf8_lossy(tiles)
+            ),
+        }
+    }
+}
+
+/// Find the loop through `S`, inferring the pipe under it from the neighbours connecting back.
+fn analyse(grid: &Grid<u8>) -> Result<Loop, Problem> {
+    let starts: Vec<_> = grid.indices().filter(|&i| grid[i] == b'S').collect();
+    let start = match starts[..] {
+        [] => return Err(Problem::MissingStart),
+        [start] => start,
+        _ => return Err(Problem::MultipleStarts(starts)),
+    };
+    let open: Vec<Dir> = Dir::ALL
+        .into_iter()
+        .filter(|d| {
+            d.step(grid, start)
+                .and_then(|i| connections(grid[i]))
+                .is_some_and(|c| c.contains(&d.opposite()))
+        })
+        .collect();
+    if open.len() < 2 {
+        return Err(Problem::DeadEnd(open));
+    }
+
+    // with three or four connecting neighbours, only some pairs may close a loop
+    let mut loops = Vec::new();
+    let mut first_problem = None;
+    for (i, &a) in open.iter().enumerate() {
+        for &b in &open[i + 1..] {
+            match trace(grid, start, a, b) {
+                Ok(cells) => loops.push(Loop {
+                    start_tile: pipe(a, b).unwrap(),
+                    cells,
+                }),
+                Err(e) => {
+                    first_problem.get_or_insert(e);
+                }
+            }
+        }
+    }
+    match loops.len() {
+        0 => Err(first_problem.unwrap()),
+        1 => Ok(loops.pop().unwrap()),
+        _ => Err(Problem::Ambiguous(
+            loops.iter().map(|l| l.start_tile).collect(),
         )),
-        _ => panic!("should not leave path"),
     }
 }
 
+/// Follow the pipes leaving `start` towards `out` until they come back in from `back`.
+fn trace(
+    grid: &Grid<u8>,
+    start: GridIndex,
+    out: Dir,
+    back: Dir,
+) -> Result<Vec<GridIndex>, Problem> {
+    let mut cells = vec![start];
+    let (mut curr, mut dir) = (start, out);
+    loop {
+        let dangling = Problem::Dangling {
+            from: curr,
+            towards: dir,
+        };
+        let next = dir.step(grid, curr).ok_or(dangling.clone())?;
+        if next == start {
+            return if dir.opposite() == back {
+                Ok(cells)
+            } else {
+                Err(dangling)
+            };
+        }
+        let [a, b] = connections(grid[next]).ok_or(dangling.clone())?;
+        dir = match dir.opposite() {
+            d if d == a => b,
+            d if d == b => a,
+            _ => return Err(dangling),
+        };
+        // without revisiting the start, the path can't cross itself, so this terminates
+        cells.push(next);
+        curr = next;
+    }
+}
+
+/// Number of tiles enclosed by the loop through the centres of the `p` tiles.
+fn enclosed_area(p: &[GridIndex]) -> i64 {
+    let vertices = p.iter().map(|i| (i.column() as i64, i.row() as i64));
+    Polygon::new(vertices.collect())
+        .interior_points()
+        .expect("grids fit in i64")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Label {
+    Loop,
+    Inside,
+    Outside,
+}
+
+/// Label every tile by casting a ray from the left edge of its row. The ray runs just above
+/// the tiles' centres, so it only crosses pipes connecting north, and running along a pipe
+/// doesn't count as crossing it. Tiles squeezed in between pipes thus still end up outside.
+fn classify(grid: &Grid<u8>, l: &Loop) -> Grid<Label> {
+    let mut labels = Grid::new(
+        grid.width(),
+        grid.height(),
+        vec![Label::Outside; grid.area()],
+    );
+    for &i in &l.cells {
+        labels[i] = Label::Loop;
+    }
+    for row in grid.rows() {
+        let mut inside = false;
+        for column in grid.columns() {
+            let i = GridIndex::new(column, row);
+            if labels[i] == Label::Loop {
+                let tile = if grid[i] == b'S' {
+                    l.start_tile
+                } else {
+                    grid[i]
+                };
+                if connections(tile).is_some_and(|c| c.contains(&Dir::North)) {
+                    inside = !inside;
+                }
+            } else if inside {
+                labels[i] = Label::Inside;
+            }
+        }
+    }
+    labels
+}
+
+/// The grid with the loop's pipes kept as they are, and every other tile replaced by `I` or `O`.
+fn render(grid: &Grid<u8>, labels: &Grid<Label>) -> String {
+    let mut out = String::new();
+    for row in grid.rows() {
+        for column in grid.columns() {
+            let i = GridIndex::new(column, row);
+            out.push(match labels[i] {
+                Label::Loop => grid[i] as char,
+                Label::Inside => 'I',
+                Label::Outside => 'O',
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn path(s: &str) -> Vec<GridIndex> {
+        analyse(&read_grid(s)).unwrap().cells
+    }
+
     #[test]
     fn can_solve_part1() {
-        assert_eq!(path(&read_grid(SAMPLE1)).len() / 2, 4);
-        assert_eq!(path(&read_grid(SAMPLE2)).len() / 2, 4);
-        assert_eq!(path(&read_grid(SAMPLE3)).len() / 2, 8);
+        assert_eq!(path(SAMPLE1).len() / 2, 4);
+        assert_eq!(path(SAMPLE2).len() / 2, 4);
+        assert_eq!(path(SAMPLE3).len() / 2, 8);
     }
     #[test]
     fn can_solve_part2() {
-        assert_eq!(enclosed_area(&read_grid(SAMPLE4)), 4);
-        assert_eq!(enclosed_area(&read_grid(SAMPLE5)), 8);
-        assert_eq!(enclosed_area(&read_grid(SAMPLE6)), 10);
+        assert_eq!(enclosed_area(&path(SAMPLE4)), 4);
+        assert_eq!(enclosed_area(&path(SAMPLE5)), 8);
+        assert_eq!(enclosed_area(&path(SAMPLE6)), 10);
+    }
+
+    #[test]
+    fn can_label_tiles() {
+        for sample in [SAMPLE1, SAMPLE2, SAMPLE3, SAMPLE4, SAMPLE5, SAMPLE6] {
+            let grid = read_grid(sample);
+            let l = analyse(&grid).unwrap();
+            let labels = classify(&grid, &l);
+            let inside = labels.cell_iter().filter(|&&x| x == Label::Inside).count();
+            assert_eq!(inside as i64, enclosed_area(&l.cells));
+            let on_loop = labels.cell_iter().filter(|&&x| x == Label::Loop).count();
+            assert_eq!(on_loop, l.cells.len());
+        }
+
+        let grid = read_grid(SAMPLE4);
+        let labels = classify(&grid, &analyse(&grid).unwrap());
+        let expected = "
+            OOOOOOOOOOO
+            OS-------7O
+            O|F-----7|O
+            O||OOOOO||O
+            O||OOOOO||O
+            O|L-7OF-J|O
+            O|II|O|II|O
+            OL--JOL--JO
+            OOOOOOOOOOO
+        ";
+        let expected: Vec<_> = expected.split_whitespace().collect();
+        assert_eq!(render(&grid, &labels), expected.join("\n") + "\n");
+    }
+
+    #[test]
+    fn can_analyse_mazes() {
+        let l = analyse(&read_grid(SAMPLE2)).unwrap();
+        assert_eq!(l.start_tile, b'F');
+        assert_eq!(
+            &l.cells[..3],
+            [(1, 1), (2, 1), (3, 1)].map(|(c, r)| GridIndex::new(c, r))
+        );
+        assert_eq!(analyse(&read_grid(SAMPLE3)).unwrap().start_tile, b'F');
+        assert_eq!(analyse(&read_grid(SAMPLE5)).unwrap().start_tile, b'F');
+
+        assert_eq!(analyse(&read_grid("...\n...")), Err(Problem::MissingStart));
+        assert_eq!(
+            analyse(&read_grid("S-7\n|.|\nL-S")),
+            Err(Problem::MultipleStarts(vec![
+                GridIndex::new(0, 0),
+                GridIndex::new(2, 2)
+            ]))
+        );
+        assert_eq!(
+            analyse(&read_grid(".S-\n...")),
+            Err(Problem::DeadEnd(vec![Dir::East]))
+        );
+        // the loop is broken to the right of the 7
+        assert_eq!(
+            analyse(&read_grid("S-7\n|.|\nL-.")),
+            Err(Problem::Dangling {
+                from: GridIndex::new(2, 1),
+                towards: Dir::South
+            })
+        );
+        // the start connects three ways, but only one pair closes the loop
+        assert_eq!(
+            analyse(&read_grid("F-S-7\nL-J.|\n....|\n..L-J")).map(|l| l.start_tile),
+            Ok(b'7')
+        );
+        // two separate loops meet at the start
+        assert_eq!(
+            analyse(&read_grid(".....\n.F7..\n.LS7.\n..LJ.\n.....")),
+            Err(Problem::Ambiguous(vec![b'J', b'F']))
+        );
     }
 
     const SAMPLE1: &str = "
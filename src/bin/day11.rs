@@ -1,35 +1,166 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, BinaryHeap};
 
 use aoc2023::read_grid;
-use rayon::prelude::*;
 use simple_grid::Grid;
 
 fn main() {
     let input = std::fs::read_to_string("inputs/day11").unwrap();
     let grid = read_grid(&input);
-    println!("Part 1: {}", solve(&grid, 2));
-    println!("Part 2: {}", solve(&grid, 1_000_000));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let number = |s: &str| -> usize { s.parse().expect("number") };
+    let index = |factor| GalaxyIndex::new(&grid, &Expansion::uniform(&grid, factor));
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => {
+            println!("Part 1: {}", solve(&grid, 2));
+            println!("Part 2: {}", solve(&grid, 1_000_000));
+        }
+        ["--diameter", factor] => println!("{}", index(number(factor)).diameter()),
+        ["--nearest", factor] => {
+            let index = index(number(factor));
+            for (i, (x, y)) in index.galaxies.iter().enumerate() {
+                let (j, d) = index.nearest(i).expect("at least two galaxies");
+                let (nx, ny) = index.galaxies[j];
+                println!("({x}, {y}) -> ({nx}, {ny}): {d}");
+            }
+        }
+        ["--closest", k, factor] => {
+            let index = index(number(factor));
+            for (d, i, j) in index.closest_pairs(number(k)) {
+                println!("{:?} - {:?}: {d}", index.galaxies[i], index.galaxies[j]);
+            }
+        }
+        _ => panic!("usage: day11 [--diameter F | --nearest F | --closest K F]"),
+    }
 }
 
 fn solve(grid: &Grid<u8>, factor: usize) -> usize {
-    let frows = free_rows(grid);
-    let fcols = free_columns(grid);
-
-    galaxies(grid)
-        .par_bridge()
-        // `flat_map_iter` instead of `flat_map` makes sure then
-        // only the outer loop is parallelized, and the inner is sequential,
-        // otherwise the overhead dwarfs any gains from parallelism
-        .flat_map_iter(|a| galaxies(grid).filter_map(move |b| (a < b).then_some((a, b))))
-        .fold(
-            || 0,
-            |sum, ((ax, ay), (bx, by))| {
-                let dy = distance(ay, by, &frows, factor);
-                let dx = distance(ax, bx, &fcols, factor);
-                sum + dx + dy
-            },
-        )
-        .sum()
+    solve_with(grid, &Expansion::uniform(grid, factor))
+}
+
+/// Sum of the distances between all pairs of galaxies, in `O(galaxies + rows + columns)`.
+/// Distances along rows and columns are independent, so each axis is summed separately.
+fn solve_with(grid: &Grid<u8>, expansion: &Expansion) -> usize {
+    let mut per_row = vec![0; grid.height()];
+    let mut per_column = vec![0; grid.width()];
+    for (x, y) in galaxies(grid) {
+        per_column[x] += 1;
+        per_row[y] += 1;
+    }
+    axis_sum(&per_row, &expansion.rows) + axis_sum(&per_column, &expansion.columns)
+}
+
+/// Sum of the distances between all pairs of points along one axis,
+/// with `counts[i]` points at position `i`, which is `widths[i]` wide.
+fn axis_sum(counts: &[usize], widths: &[usize]) -> usize {
+    let (mut coordinate, mut seen, mut seen_sum, mut total) = (0, 0, 0, 0);
+    for (&count, &width) in counts.iter().zip(widths) {
+        // every point here is `coordinate - c` away from each point seen before at `c`
+        total += count * (coordinate * seen - seen_sum);
+        seen += count;
+        seen_sum += count * coordinate;
+        coordinate += width;
+    }
+    total
+}
+
+/// How wide each row and column of the image is after expansion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Expansion {
+    rows: Vec<usize>,
+    columns: Vec<usize>,
+}
+
+impl Expansion {
+    /// Every free row and column becomes `factor` times as wide, the rest stay as they are.
+    fn uniform(grid: &Grid<u8>, factor: usize) -> Self {
+        let (frows, fcols) = (free_rows(grid), free_columns(grid));
+        let width = |free: &BTreeSet<usize>, i| if free.contains(&i) { factor } else { 1 };
+        Self {
+            rows: grid.rows().map(|i| width(&frows, i)).collect(),
+            columns: grid.columns().map(|i| width(&fcols, i)).collect(),
+        }
+    }
+}
+
+/// Galaxies in expanded coordinates, sorted by column so queries can sweep across them
+/// and stop as soon as the column distance alone rules out anything further away.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct GalaxyIndex {
+    galaxies: Vec<(usize, usize)>,
+}
+
+impl GalaxyIndex {
+    fn new(grid: &Grid<u8>, expansion: &Expansion) -> Self {
+        let starts = |widths: &[usize]| -> Vec<usize> {
+            widths
+                .iter()
+                .scan(0, |start, w| Some(std::mem::replace(start, *start + w)))
+                .collect()
+        };
+        let (xs, ys) = (starts(&expansion.columns), starts(&expansion.rows));
+        let mut galaxies: Vec<_> = galaxies(grid).map(|(x, y)| (xs[x], ys[y])).collect();
+        galaxies.sort_unstable();
+        Self { galaxies }
+    }
+
+    fn distance(&self, i: usize, j: usize) -> usize {
+        let ((ax, ay), (bx, by)) = (self.galaxies[i], self.galaxies[j]);
+        ax.abs_diff(bx) + ay.abs_diff(by)
+    }
+
+    /// The galaxy closest to galaxy `i`, and its distance.
+    fn nearest(&self, i: usize) -> Option<(usize, usize)> {
+        let x = self.galaxies[i].0;
+        let mut best: Option<(usize, usize)> = None;
+        let mut sweep = |side: &mut dyn Iterator<Item = usize>| {
+            for j in side {
+                if best.is_some_and(|(_, d)| self.galaxies[j].0.abs_diff(x) >= d) {
+                    break;
+                }
+                let d = self.distance(i, j);
+                if best.is_none_or(|(_, b)| d < b) {
+                    best = Some((j, d));
+                }
+            }
+        };
+        sweep(&mut (0..i).rev());
+        sweep(&mut (i + 1..self.galaxies.len()));
+        best
+    }
+
+    /// The `k` pairs of galaxies closest to each other, as `(distance, i, j)` with `i < j`, nearest first.
+    fn closest_pairs(&self, k: usize) -> Vec<(usize, usize, usize)> {
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        for i in 0..self.galaxies.len() {
+            for j in i + 1..self.galaxies.len() {
+                let dx = self.galaxies[j].0 - self.galaxies[i].0;
+                if heap.len() == k && heap.peek().is_none_or(|&(d, _, _)| dx >= d) {
+                    break;
+                }
+                heap.push((self.distance(i, j), i, j));
+                if heap.len() > k {
+                    heap.pop();
+                }
+            }
+        }
+        heap.into_sorted_vec()
+    }
+
+    /// The largest distance between two galaxies. With `s = x + y` and `d = x - y`,
+    /// the Manhattan distance is `max(|s1 - s2|, |d1 - d2|)`, so only the extremes matter.
+    fn diameter(&self) -> usize {
+        let spread = |values: Vec<i64>| {
+            let (min, max) = (values.iter().min(), values.iter().max());
+            min.zip(max).map_or(0, |(min, max)| (max - min) as usize)
+        };
+        let sums = self.galaxies.iter().map(|&(x, y)| (x + y) as i64).collect();
+        let diffs = self
+            .galaxies
+            .iter()
+            .map(|&(x, y)| x as i64 - y as i64)
+            .collect();
+        spread(sums).max(spread(diffs))
+    }
 }
 
 fn galaxies(grid: &Grid<u8>) -> impl Iterator<Item = (usize, usize)> + '_ {
@@ -49,10 +180,6 @@ fn free_columns(grid: &Grid<u8>) -> BTreeSet<usize> {
         .collect()
 }
 
-fn distance(x1: usize, x2: usize, free_space: &BTreeSet<usize>, factor: usize) -> usize {
-    x1.abs_diff(x2) + free_space.range(x1.min(x2)..x1.max(x2)).count() * (factor - 1)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -70,6 +197,53 @@ mod tests {
         assert_eq!(solve(&grid, 100), 8410);
     }
 
+    #[test]
+    fn can_expand_unevenly() {
+        let grid = read_grid(SAMPLE1);
+        let expansion = Expansion {
+            rows: (1..=10).collect(),
+            columns: vec![3, 1, 4, 1, 5, 9, 2, 6, 5, 3],
+        };
+        // compare with summing over all pairs in expanded coordinates
+        let start = |widths: &[usize], i: usize| widths[..i].iter().sum::<usize>();
+        let expanded: Vec<_> = galaxies(&grid)
+            .map(|(x, y)| (start(&expansion.columns, x), start(&expansion.rows, y)))
+            .collect();
+        let brute_force: usize = expanded
+            .iter()
+            .enumerate()
+            .flat_map(|(i, a)| expanded[i + 1..].iter().map(move |b| (a, b)))
+            .map(|((ax, ay), (bx, by))| ax.abs_diff(*bx) + ay.abs_diff(*by))
+            .sum();
+        assert_eq!(solve_with(&grid, &expansion), brute_force);
+    }
+
+    #[test]
+    fn can_query_galaxies() {
+        let grid = read_grid(SAMPLE1);
+        for factor in [1, 2, 100] {
+            let index = GalaxyIndex::new(&grid, &Expansion::uniform(&grid, factor));
+            let n = index.galaxies.len();
+            let mut pairs: Vec<_> = (0..n)
+                .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+                .map(|(i, j)| (index.distance(i, j), i, j))
+                .collect();
+            pairs.sort_unstable();
+
+            assert_eq!(index.diameter(), pairs.last().unwrap().0);
+            assert_eq!(index.closest_pairs(5), pairs[..5]);
+            assert_eq!(index.closest_pairs(100), pairs);
+            assert_eq!(index.closest_pairs(0), []);
+            for i in 0..n {
+                let nearest = (0..n)
+                    .filter(|&j| j != i)
+                    .map(|j| index.distance(i, j))
+                    .min();
+                assert_eq!(index.nearest(i).map(|(_, d)| d), nearest);
+            }
+        }
+    }
+
     const SAMPLE1: &str = "
         ...#......
         .......#..
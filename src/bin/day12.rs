@@ -1,25 +1,87 @@
-use std::{hash::BuildHasherDefault, iter::repeat};
+use std::{hash::BuildHasherDefault, iter::repeat_n};
 
 use fxhash::FxHasher;
 use itertools::Itertools;
+use num_bigint::BigUint;
+use num_traits::{CheckedAdd, One, Zero};
+use rand::Rng;
+use simple_grid::{Grid, GridIndex};
 
 type FxHashMap<K, V> = hashbrown::HashMap<K, V, BuildHasherDefault<FxHasher>>;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let ["--nonogram", path] = args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        let puzzle = std::fs::read_to_string(path).unwrap();
+        match Nonogram::parse(&puzzle).solve() {
+            Solution::Unique(grid) => print!("{}", render(&grid)),
+            Solution::Multiple(a, b) => {
+                println!("multiple solutions, for example:\n{}", render(&a));
+                print!("and:\n{}", render(&b));
+            }
+            Solution::None => println!("no solution"),
+        }
+        return;
+    }
     let input = std::fs::read_to_string("inputs/day12").unwrap();
+    // the 1-based line of the input the other flags look at
+    let line = |n: &str| {
+        parse_line(
+            input
+                .trim()
+                .lines()
+                .nth(n.parse::<usize>().unwrap() - 1)
+                .unwrap(),
+        )
+    };
 
-    println!("Part 1: {}", solve_part1(&input));
-    println!("Part 2: {}", solve_part2(&input));
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => {
+            println!("Part 1: {}", solve_part1(&input));
+            println!("Part 2: {}", solve_part2(&input));
+        }
+        ["--unfold", times] => {
+            let times = times.parse().expect("number of copies");
+            println!("{}", solve_unfolded::<BigUint>(&input, times).unwrap());
+        }
+        ["--list", n] => Arrangements::new(&line(n))
+            .iter()
+            .for_each(|a| println!("{a}")),
+        ["--sample", n] => {
+            let sample = Arrangements::new(&line(n)).sample(&mut rand::thread_rng());
+            println!("{}", sample.unwrap_or_else(|| "no arrangements".to_owned()));
+        }
+        ["--odds", n] => {
+            let line = line(n);
+            match probabilities(&line) {
+                Some(odds) => {
+                    for (c, p) in line.pattern.chars().zip(odds) {
+                        println!("{c} {p:.3}");
+                    }
+                }
+                None => println!("no arrangements"),
+            }
+        }
+        _ => {
+            panic!("usage: day12 [--nonogram FILE | --unfold K | --list N | --sample N | --odds N]")
+        }
+    }
 }
 
 fn solve_part1(input: &str) -> usize {
-    let lines = input.trim().lines().map(parse_line);
-    lines.map(count).sum()
+    solve_unfolded(input, 1).expect("fits in usize")
 }
 
 fn solve_part2(input: &str) -> usize {
+    solve_unfolded(input, 5).expect("fits in usize")
+}
+
+/// Sum of the arrangements of every line unfolded `times` times, or `None` on overflow.
+fn solve_unfolded<T: Clone + Zero + One + CheckedAdd>(input: &str, times: usize) -> Option<T> {
     let lines = input.trim().lines().map(parse_line);
-    lines.map(|l| count(quintuple(l))).sum()
+    lines
+        .map(|l| unfold(l, times))
+        .try_fold(T::zero(), |acc, l| acc.checked_add(&count_table(&l)?))
 }
 
 fn parse_line(s: &str) -> Line {
@@ -29,17 +91,55 @@ fn parse_line(s: &str) -> Line {
     Line { pattern, nums }
 }
 
+#[derive(Debug, Clone)]
 struct Line {
     pattern: String,
     nums: Vec<usize>,
 }
 
-fn quintuple(line: Line) -> Line {
-    let pattern = repeat(line.pattern.as_str()).take(5).join("?");
-    let nums = line.nums.repeat(5);
+fn unfold(line: Line, times: usize) -> Line {
+    let pattern = repeat_n(line.pattern.as_str(), times).join("?");
+    let nums = line.nums.repeat(times);
     Line { pattern, nums }
 }
 
+/// Count the arrangements with a table over (position, run index) instead of [`count_memo`]'s
+/// hash map, without allocating along the way. Returns `None` if the count overflows `T`.
+fn count_table<T: Clone + Zero + One + CheckedAdd>(line: &Line) -> Option<T> {
+    let pattern = line.pattern.as_bytes();
+    let (len, runs) = (pattern.len(), line.nums.len());
+    // the first operational spring at or after each position, so runs are checked in O(1)
+    let mut next_dot = vec![len; len + 1];
+    for p in (0..len).rev() {
+        next_dot[p] = if pattern[p] == b'.' {
+            p
+        } else {
+            next_dot[p + 1]
+        };
+    }
+    // ways[p * (runs + 1) + g] counts the arrangements of pattern[p..] with the runs nums[g..],
+    // where a run may start at p
+    let at = |p: usize, g: usize| p * (runs + 1) + g;
+    let mut ways = vec![T::zero(); (len + 1) * (runs + 1)];
+    ways[at(len, runs)] = T::one();
+    for p in (0..len).rev() {
+        for g in 0..=runs {
+            let mut total = T::zero();
+            if pattern[p] != b'#' {
+                total = ways[at(p + 1, g)].clone();
+            }
+            if let Some(&n) = line.nums.get(g) {
+                let end = p + n;
+                if end <= len && next_dot[p] >= end && pattern.get(end) != Some(&b'#') {
+                    total = total.checked_add(&ways[at((end + 1).min(len), g + 1)])?;
+                }
+            }
+            ways[at(p, g)] = total;
+        }
+    }
+    Some(ways[0].clone())
+}
+
 fn count(mut line: Line) -> usize {
     let mut pattern = trim_end(line.pattern.as_bytes().to_vec());
     // reverse the vectors since we'd otherwise be popping from the front
@@ -118,9 +218,278 @@ fn trim_end(mut pattern: Vec<u8>) -> Vec<u8> {
     pattern
 }
 
+/// The number of ways to complete every suffix of a line, used as weights to walk through its arrangements.
+struct Arrangements {
+    pattern: Vec<u8>,
+    nums: Vec<usize>,
+    /// `ways[p][g]` counts the arrangements of `pattern[p..]` with the runs `nums[g..]`,
+    /// where a run may start at `p`
+    ways: Vec<Vec<usize>>,
+}
+
+impl Arrangements {
+    fn new(line: &Line) -> Self {
+        let pattern = line.pattern.as_bytes().to_vec();
+        let nums = line.nums.clone();
+        let mut ways = vec![vec![0; nums.len() + 1]; pattern.len() + 1];
+        ways[pattern.len()][nums.len()] = 1;
+        let mut this = Self {
+            pattern,
+            nums,
+            ways: Vec::new(),
+        };
+        for p in (0..this.pattern.len()).rev() {
+            for g in 0..=this.nums.len() {
+                ways[p][g] = this.choices(p, g).map(|(_, (q, h))| ways[q][h]).sum();
+            }
+        }
+        this.ways = ways;
+        this
+    }
+
+    /// What can go at position `p` when the runs from `g` on are left: either an operational spring,
+    /// or the whole next run followed by its separator. Each with the number of damaged springs placed,
+    /// and the position and run to continue from.
+    fn choices(&self, p: usize, g: usize) -> impl Iterator<Item = (usize, (usize, usize))> + '_ {
+        let len = self.pattern.len();
+        let empty = (self.pattern[p] != b'#').then_some((0, (p + 1, g)));
+        let run = self.nums.get(g).and_then(|&n| {
+            let end = p + n;
+            let fits = end <= len
+                && !self.pattern[p..end].contains(&b'.')
+                && self.pattern.get(end) != Some(&b'#');
+            fits.then_some((n, ((end + 1).min(len), g + 1)))
+        });
+        empty.into_iter().chain(run)
+    }
+
+    fn total(&self) -> usize {
+        self.ways[0][0]
+    }
+
+    /// Every arrangement, one at a time.
+    fn iter(&self) -> impl Iterator<Item = String> + '_ {
+        // without a single arrangement even the start is a dead end
+        let mut stack = Vec::new();
+        if self.total() > 0 {
+            stack.push((0, 0, String::new()));
+        }
+        std::iter::from_fn(move || {
+            while let Some((p, g, done)) = stack.pop() {
+                if p == self.pattern.len() {
+                    return Some(done);
+                }
+                let choices: Vec<_> = self.choices(p, g).collect();
+                for (damaged, (q, h)) in choices.into_iter().rev() {
+                    // dead ends are pruned right away, so every path on the stack leads somewhere
+                    if self.ways[q][h] > 0 {
+                        stack.push((q, h, extend(&done, damaged, q)));
+                    }
+                }
+            }
+            None
+        })
+    }
+
+    /// A uniformly random arrangement, or `None` if there are none.
+    fn sample(&self, rng: &mut impl Rng) -> Option<String> {
+        if self.total() == 0 {
+            return None;
+        }
+        let (mut p, mut g, mut done) = (0, 0, String::new());
+        while p < self.pattern.len() {
+            let mut pick = rng.gen_range(0..self.ways[p][g]);
+            let (damaged, (q, h)) = self
+                .choices(p, g)
+                .find(|&(_, (q, h))| {
+                    let found = pick < self.ways[q][h];
+                    pick = pick.wrapping_sub(self.ways[q][h]);
+                    found
+                })
+                .expect("the weights add up to the total");
+            done = extend(&done, damaged, q);
+            (p, g) = (q, h);
+        }
+        Some(done)
+    }
+}
+
+/// Add `damaged` springs, and then operational ones up to position `q`.
+fn extend(done: &str, damaged: usize, q: usize) -> String {
+    let mut done = done.to_owned();
+    done.extend(repeat_n('#', damaged));
+    done.extend(repeat_n('.', q - done.len()));
+    done
+}
+
+/// The chance that each position is damaged, over all the arrangements of the line,
+/// or `None` if there are none.
+fn probabilities(line: &Line) -> Option<Vec<f64>> {
+    let total = count(line.clone());
+    if total == 0 {
+        return None;
+    }
+    let total = total as f64;
+    let odds = (0..line.pattern.len())
+        .map(|i| match line.pattern.as_bytes()[i] {
+            b'?' => {
+                let mut damaged = line.clone();
+                damaged.pattern.replace_range(i..=i, "#");
+                count(damaged) as f64 / total
+            }
+            b'#' => 1.0,
+            _ => 0.0,
+        })
+        .collect();
+    Some(odds)
+}
+
+/// A nonogram: the runs of filled cells in every row and column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Nonogram {
+    rows: Vec<Vec<usize>>,
+    columns: Vec<Vec<usize>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Solution {
+    Unique(Grid<u8>),
+    /// Two of the solutions
+    Multiple(Grid<u8>, Grid<u8>),
+    None,
+}
+
+impl Nonogram {
+    /// One line of comma separated runs per row, a blank line, and then the same for every column.
+    /// An empty row or column is written as `0`.
+    fn parse(input: &str) -> Self {
+        let (rows, columns) = input.trim().split_once("\n\n").expect("rows and columns");
+        let clues = |s: &str| -> Vec<Vec<usize>> {
+            s.lines()
+                .map(|l| {
+                    l.trim()
+                        .split(',')
+                        .map(|n| n.parse().expect("run length"))
+                        .filter(|&n| n > 0)
+                        .collect()
+                })
+                .collect()
+        };
+        Self {
+            rows: clues(rows),
+            columns: clues(columns),
+        }
+    }
+
+    /// Deduce what the clues allow line by line, and guess when that gets stuck.
+    fn solve(&self) -> Solution {
+        let unknown = Grid::new(
+            self.columns.len(),
+            self.rows.len(),
+            vec![b'?'; self.rows.len() * self.columns.len()],
+        );
+        let mut found = Vec::new();
+        self.search(unknown, &mut found);
+        let mut found = found.into_iter();
+        match (found.next(), found.next()) {
+            (None, _) => Solution::None,
+            (Some(a), None) => Solution::Unique(a),
+            (Some(a), Some(b)) => Solution::Multiple(a, b),
+        }
+    }
+
+    /// Collect up to two solutions consistent with the cells known so far.
+    fn search(&self, mut grid: Grid<u8>, found: &mut Vec<Grid<u8>>) {
+        if !self.propagate(&mut grid) {
+            return;
+        }
+        let Some(i) = grid.position(|&c| c == b'?') else {
+            found.push(grid);
+            return;
+        };
+        for guess in [b'#', b'.'] {
+            if found.len() >= 2 {
+                return;
+            }
+            let mut grid = grid.clone();
+            grid[i] = guess;
+            self.search(grid, found);
+        }
+    }
+
+    /// Fill in every cell that a single row or column forces, until nothing changes.
+    /// Returns `false` if some line can't be completed anymore.
+    fn propagate(&self, grid: &mut Grid<u8>) -> bool {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (row, clue) in self.rows.iter().enumerate() {
+                let cells: Vec<_> = grid.row_iter(row).copied().collect();
+                let Some(solved) = solve_line(&cells, clue) else {
+                    return false;
+                };
+                for (column, c) in solved.into_iter().enumerate() {
+                    changed |= grid[GridIndex::new(column, row)] != c;
+                    grid[GridIndex::new(column, row)] = c;
+                }
+            }
+            for (column, clue) in self.columns.iter().enumerate() {
+                let cells: Vec<_> = grid.column_iter(column).copied().collect();
+                let Some(solved) = solve_line(&cells, clue) else {
+                    return false;
+                };
+                for (row, c) in solved.into_iter().enumerate() {
+                    changed |= grid[GridIndex::new(column, row)] != c;
+                    grid[GridIndex::new(column, row)] = c;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Settle every unknown cell of a line that is the same in all its arrangements,
+/// or `None` if there are no arrangements at all.
+fn solve_line(cells: &[u8], clue: &[usize]) -> Option<Vec<u8>> {
+    let arrangements = |cells: &[u8]| {
+        count(Line {
+            pattern: String::from_utf8(cells.to_vec()).unwrap(),
+            nums: clue.to_vec(),
+        })
+    };
+    if arrangements(cells) == 0 {
+        return None;
+    }
+    let mut solved = cells.to_vec();
+    let mut trial = cells.to_vec();
+    for (i, &c) in cells.iter().enumerate() {
+        if c != b'?' {
+            continue;
+        }
+        trial[i] = b'#';
+        let filled = arrangements(&trial);
+        trial[i] = b'.';
+        let empty = arrangements(&trial);
+        trial[i] = b'?';
+        if filled == 0 {
+            solved[i] = b'.';
+        } else if empty == 0 {
+            solved[i] = b'#';
+        }
+    }
+    Some(solved)
+}
+
+fn render(grid: &Grid<u8>) -> String {
+    grid.rows()
+        .map(|row| String::from_utf8(grid.row_iter(row).copied().collect()).unwrap() + "\n")
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::SeedableRng;
 
     #[test]
     fn pattern2() {
@@ -140,27 +509,126 @@ mod tests {
         assert_eq!(count(line), 1);
     }
 
+    #[test]
+    fn can_list_and_sample_arrangements() {
+        let line = parse_line("?###???????? 3,2,1");
+        let arrangements = Arrangements::new(&line);
+        assert_eq!(arrangements.total(), 10);
+        let all: Vec<_> = arrangements.iter().collect();
+        assert_eq!(all.len(), 10);
+        assert_eq!(all[0], ".###....##.#");
+        assert!(all.iter().all_unique());
+        for a in &all {
+            let runs: Vec<_> = a
+                .split('.')
+                .filter(|r| !r.is_empty())
+                .map(str::len)
+                .collect();
+            assert_eq!(runs, line.nums);
+            let fits = a
+                .bytes()
+                .zip(line.pattern.bytes())
+                .all(|(a, p)| p == b'?' || a == p);
+            assert!(fits, "{a} doesn't fit the pattern");
+        }
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(12);
+        let mut seen = FxHashMap::default();
+        for _ in 0..10_000 {
+            *seen
+                .entry(arrangements.sample(&mut rng).unwrap())
+                .or_insert(0) += 1;
+        }
+        assert_eq!(seen.len(), 10);
+        assert!(seen.values().all(|&n| (800..1200).contains(&n)));
+
+        for line in [
+            "???.### 1,1,3",
+            ".??..??...?##. 1,1,3",
+            "?#?#?#?#?#?#?#? 1,3,1,6",
+        ] {
+            let line = parse_line(line);
+            assert_eq!(Arrangements::new(&line).total(), count(line));
+        }
+        let none = Arrangements::new(&parse_line("#.# 3"));
+        assert_eq!((none.iter().count(), none.sample(&mut rng)), (0, None));
+        let empty = |nums: &[usize]| Line {
+            pattern: String::new(),
+            nums: nums.to_vec(),
+        };
+        assert_eq!(Arrangements::new(&empty(&[1])).iter().count(), 0);
+        assert_eq!(Arrangements::new(&empty(&[])).iter().collect_vec(), [""]);
+
+        // the last # is the second 1, so the spot before it is operational,
+        // and the first 1 is in either of the first two spots
+        let odds = probabilities(&parse_line("??.?# 1,1"));
+        assert_eq!(odds, Some(vec![0.5, 0.5, 0.0, 0.0, 1.0]));
+        assert_eq!(probabilities(&parse_line("#?.# 3")), None);
+    }
+
+    #[test]
+    fn table_matches_memo() {
+        for line in SAMPLE1.trim().lines() {
+            for times in 1..=5 {
+                let line = unfold(parse_line(line), times);
+                assert_eq!(count_table::<usize>(&line), Some(count(line)));
+            }
+        }
+        assert_eq!(count_table::<u8>(&parse_line("?????????? 1")), Some(10));
+        assert_eq!(count_table::<u128>(&parse_line("#.# 3")), Some(0));
+    }
+
+    #[test]
+    fn can_unfold_further() {
+        let wide = solve_unfolded::<BigUint>(SAMPLE1, 20).unwrap();
+        assert_eq!(
+            solve_unfolded::<u128>(SAMPLE1, 20).map(BigUint::from),
+            Some(wide)
+        );
+        // ?###???????? alone has 10 * 15^(k-1) arrangements unfolded k times
+        let huge = solve_unfolded::<BigUint>("?###???????? 3,2,1", 50).unwrap();
+        assert_eq!(huge, BigUint::from(10u32) * BigUint::from(15u32).pow(49));
+        assert_eq!(solve_unfolded::<u128>("?###???????? 3,2,1", 50), None);
+        assert_eq!(solve_unfolded::<u64>(SAMPLE1, 5), Some(525152));
+    }
+
+    #[test]
+    fn can_solve_nonograms() {
+        let letter = Nonogram::parse("3\n1,1\n5\n1,1\n1,1\n\n4\n1,1\n1,1\n1,1\n4");
+        let Solution::Unique(grid) = letter.solve() else {
+            panic!("expected a unique solution");
+        };
+        assert_eq!(render(&grid), ".###.\n#...#\n#####\n#...#\n#...#\n");
+
+        // either diagonal works
+        let diagonal = Nonogram::parse("1\n1\n\n1\n1");
+        let Solution::Multiple(a, b) = diagonal.solve() else {
+            panic!("expected multiple solutions");
+        };
+        assert_eq!(
+            (render(&a), render(&b)),
+            ("#.\n.#\n".into(), ".#\n#.\n".into())
+        );
+
+        let impossible = Nonogram::parse("2\n0\n\n1\n0");
+        assert_eq!(impossible.solve(), Solution::None);
+    }
+
     #[test]
     fn sample1_part1() {
-        let sample = "
-            ???.### 1,1,3
-            .??..??...?##. 1,1,3
-            ?#?#?#?#?#?#?#? 1,3,1,6
-            ????.#...#... 4,1,1
-            ????.######..#####. 1,6,5
-            ?###???????? 3,2,1";
-        assert_eq!(solve_part1(sample), 21);
+        assert_eq!(solve_part1(SAMPLE1), 21);
     }
 
     #[test]
     fn sample1_part2() {
-        let sample = "
-            ???.### 1,1,3
-            .??..??...?##. 1,1,3
-            ?#?#?#?#?#?#?#? 1,3,1,6
-            ????.#...#... 4,1,1
-            ????.######..#####. 1,6,5
-            ?###???????? 3,2,1";
-        assert_eq!(solve_part2(sample), 525152);
+        assert_eq!(solve_part2(SAMPLE1), 525152);
     }
+
+    const SAMPLE1: &str = "
+        ???.### 1,1,3
+        .??..??...?##. 1,1,3
+        ?#?#?#?#?#?#?#? 1,3,1,6
+        ????.#...#... 4,1,1
+        ????.######..#####. 1,6,5
+        ?###???????? 3,2,1";
 }
@@ -1,5 +1,6 @@
 use std::iter::successors;
 
+use aoc2023::polygon::Polygon;
 use winnow::{
     ascii::{dec_int, hex_uint, space1},
     combinator::{alt, delimited, seq},
@@ -84,18 +85,11 @@ fn dig_path(mut entires: impl Iterator<Item = Entry>) -> Vec<(i64, i64)> {
     .collect()
 }
 
-// Based on: https://en.wikipedia.org/wiki/Shoelace_formula
+/// Number of cubes dug out along the closed path `p` and inside it.
 fn enclosed_area(p: &[(i64, i64)]) -> i64 {
-    let n = p.len();
-    let product = (0..n)
-        .map(|i| p[i].0 * (p[(i + 1) % n].1 - p[(i + n) % n].1))
-        .sum::<i64>();
-    let perimeter = p
-        .iter()
-        .zip(&p[1..])
-        .map(|((x0, y0), (x1, y1))| (x0 - *x1).abs() + (y0 - *y1).abs())
-        .sum::<i64>();
-    product.abs() + (perimeter / 2) + 1
+    Polygon::new(p.to_vec())
+        .lattice_points()
+        .expect("lagoon too large for i64")
 }
 
 #[inline]
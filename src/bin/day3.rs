@@ -1,74 +1,377 @@
 use aoc2023::read_grid_with;
 use simple_grid::{Grid, GridIndex};
+use std::fmt::Write;
+use std::ops::Range;
+use std::str::FromStr;
 
 fn main() {
     let input = std::fs::read_to_string("inputs/day3").unwrap();
-    println!("Part1: {}", solve_part1(&input));
-    println!("Part2: {}", solve_part2(&input));
-}
-
-fn solve_part1(input: &str) -> u32 {
-    let grid = read_grid_with(input, |b| *b as char);
-    let (w, h) = grid.dimensions();
-    let mut visited = Grid::new(w, h, vec![false; w * h]);
-    grid.cells_with_indices_iter()
-        .filter_map(|(i, c)| (c != &'.' && c.is_ascii_punctuation()).then_some(i))
-        .map(|symbol| {
-            let mut sum: u32 = 0;
-            for nbor in grid.neighbor_indices_of(symbol) {
-                if visited[nbor] || !grid[nbor].is_ascii_digit() {
-                    continue;
-                }
-                sum += number_at(nbor, &grid, &mut visited);
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().is_none() {
+        println!("Part1: {}", solve_part1(&input));
+        println!("Part2: {}", solve_part2(&input));
+        return;
+    }
+
+    let mut rules = RuleSet::part2();
+    let mut custom_rules = Vec::new();
+    let mut export = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dot" | "--json" => export = Some(arg),
+            "--adjacency" => rules.adjacency = args.next().expect("4 or 8").parse().unwrap(),
+            "--rule" => custom_rules.push(args.next().expect("rule").parse().unwrap()),
+            _ => panic!("unexpected argument: {arg}"),
+        }
+    }
+    if !custom_rules.is_empty() {
+        rules.rules = custom_rules;
+    }
+
+    let schematic = Schematic::parse_with(&input, rules.adjacency).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1)
+    });
+    match export.as_deref() {
+        Some("--dot") => print!("{}", schematic.to_dot()),
+        Some("--json") => println!("{}", schematic.to_json()),
+        _ => {
+            match schematic.part_total() {
+                Some(parts) => println!("Part numbers: {parts}"),
+                None => println!("Part numbers: overflow"),
+            }
+            match schematic.gear_total(&rules.rules) {
+                Some(gears) => println!("Gears: {gears}"),
+                None => println!("Gears: overflow"),
             }
-            sum
+        }
+    }
+}
+
+fn solve_part1(input: &str) -> u64 {
+    Schematic::parse(input)
+        .part_total()
+        .expect("part numbers overflow u64")
+}
+
+fn solve_part2(input: &str) -> u64 {
+    RuleSet::part2()
+        .evaluate(input)
+        .expect("gear ratios overflow u64")
+}
+
+fn is_symbol(c: char) -> bool {
+    c != '.' && c.is_ascii_punctuation()
+}
+
+/// Which cells around a digit count as touching it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Adjacency {
+    /// Horizontal, vertical and diagonal neighbours.
+    #[default]
+    Eight,
+    /// Horizontal and vertical neighbours only.
+    Four,
+}
+
+impl FromStr for Adjacency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "8" => Ok(Adjacency::Eight),
+            "4" => Ok(Adjacency::Four),
+            _ => Err(format!("adjacency must be 4 or 8, got {s:?}")),
+        }
+    }
+}
+
+/// How many numbers a symbol has to touch for a rule to apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Requirement {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+impl Requirement {
+    fn is_met_by(self, count: usize) -> bool {
+        match self {
+            Requirement::Exactly(n) => count == n,
+            Requirement::AtLeast(n) => count >= n,
+        }
+    }
+}
+
+/// How the numbers around a matching symbol are combined into a single value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Aggregation {
+    Product,
+    Sum,
+    Max,
+}
+
+impl Aggregation {
+    /// `None` if the result overflows.
+    fn apply(self, mut values: impl Iterator<Item = u64>) -> Option<u64> {
+        match self {
+            Aggregation::Product => values.try_fold(1u64, |acc, v| acc.checked_mul(v)),
+            Aggregation::Sum => values.try_fold(0u64, |acc, v| acc.checked_add(v)),
+            Aggregation::Max => Some(values.max().unwrap_or(0)),
+        }
+    }
+}
+
+/// A gear rule for one class of symbols.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct GearRule {
+    /// Every glyph belonging to the symbol class.
+    glyphs: String,
+    requirement: Requirement,
+    aggregation: Aggregation,
+}
+
+impl FromStr for GearRule {
+    type Err = String;
+
+    /// Parse a rule written as `GLYPHS:N:AGGREGATION`, where `N` may be suffixed
+    /// with `+` to mean "at least", e.g. `*:2:product` or `#$:1+:sum`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.rsplitn(3, ':');
+        let (Some(aggregation), Some(count), Some(glyphs)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(format!("expected GLYPHS:N:AGGREGATION, got {s:?}"));
+        };
+        let parse_count = |n: &str| n.parse().map_err(|e| format!("{n:?}: {e}"));
+        let requirement = match count.strip_suffix('+') {
+            Some(n) => Requirement::AtLeast(parse_count(n)?),
+            None => Requirement::Exactly(parse_count(count)?),
+        };
+        let aggregation = match aggregation {
+            "product" => Aggregation::Product,
+            "sum" => Aggregation::Sum,
+            "max" => Aggregation::Max,
+            _ => return Err(format!("unknown aggregation {aggregation:?}")),
+        };
+        Ok(GearRule {
+            glyphs: glyphs.to_owned(),
+            requirement,
+            aggregation,
         })
-        .sum()
-}
-
-fn solve_part2(input: &str) -> u32 {
-    let grid = read_grid_with(input, |b| *b as char);
-    let (w, h) = grid.dimensions();
-    let mut visited = Grid::new(w, h, vec![false; w * h]);
-    grid.cells_with_indices_iter()
-        .filter_map(|(i, &c)| (c == '*').then_some(i))
-        .map(|gear| {
-            let mut nums = Vec::new();
-            for nbor in grid.neighbor_indices_of(gear) {
-                if visited[nbor] || !grid[nbor].is_ascii_digit() {
+    }
+}
+
+/// Everything needed to evaluate the gears of a (variant) schematic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RuleSet {
+    adjacency: Adjacency,
+    rules: Vec<GearRule>,
+}
+
+impl RuleSet {
+    /// The puzzle's rules: a `*` touching exactly two numbers is a gear, and its ratio is their product.
+    fn part2() -> Self {
+        RuleSet {
+            adjacency: Adjacency::Eight,
+            rules: vec![GearRule {
+                glyphs: "*".to_owned(),
+                requirement: Requirement::Exactly(2),
+                aggregation: Aggregation::Product,
+            }],
+        }
+    }
+
+    /// Sum of all the gear values, or `None` if it or any of the numbers overflows.
+    fn evaluate(&self, input: &str) -> Option<u64> {
+        Schematic::parse_with(input, self.adjacency)
+            .ok()?
+            .gear_total(&self.rules)
+    }
+}
+
+/// A number printed in the schematic, spanning `columns` of a single `row`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Number {
+    value: u64,
+    row: usize,
+    columns: Range<usize>,
+    /// Indices into [`Schematic::symbols`]
+    symbols: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Symbol {
+    glyph: char,
+    position: GridIndex,
+    /// Indices into [`Schematic::numbers`]
+    numbers: Vec<usize>,
+}
+
+/// Bipartite adjacency graph between the numbers and the symbols of an engine schematic.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Schematic {
+    numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+}
+
+impl Schematic {
+    fn parse(input: &str) -> Self {
+        Self::parse_with(input, Adjacency::Eight).expect("numbers fit into u64")
+    }
+
+    /// Fails if a number doesn't fit into a `u64`.
+    fn parse_with(input: &str, adjacency: Adjacency) -> Result<Self, String> {
+        let grid = read_grid_with(input, |b| *b as char);
+        let (w, h) = grid.dimensions();
+
+        let mut symbol_ids = Grid::new(w, h, vec![None; w * h]);
+        let mut symbols = Vec::new();
+        for (i, &c) in grid
+            .cells_with_indices_iter()
+            .filter(|(_, c)| is_symbol(**c))
+        {
+            symbol_ids[i] = Some(symbols.len());
+            symbols.push(Symbol {
+                glyph: c,
+                position: i,
+                numbers: Vec::new(),
+            });
+        }
+
+        let mut numbers = Vec::new();
+        for row in grid.rows() {
+            let mut col = 0;
+            while col < w {
+                if !grid[(col, row)].is_ascii_digit() {
+                    col += 1;
                     continue;
                 }
-                nums.push(number_at(nbor, &grid, &mut visited));
-            }
-            if nums.len() == 2 {
-                nums.into_iter().product::<u32>()
-            } else {
-                0
+                let start = col;
+                let mut value = 0u64;
+                while let Some(d) = grid.get((col, row)).and_then(|c| c.to_digit(10)) {
+                    value = value
+                        .checked_mul(10)
+                        .and_then(|v| v.checked_add(d as u64))
+                        .ok_or_else(|| {
+                            format!("the number at row {row}, column {start} doesn't fit into u64")
+                        })?;
+                    col += 1;
+                }
+                let mut adjacent = (start..col)
+                    .flat_map(|c| -> Box<dyn Iterator<Item = GridIndex>> {
+                        match adjacency {
+                            Adjacency::Eight => Box::new(grid.neighbor_indices_of((c, row))),
+                            Adjacency::Four => {
+                                Box::new(grid.cardinal_neighbor_indices_of((c, row)))
+                            }
+                        }
+                    })
+                    .filter_map(|i| symbol_ids[i])
+                    .collect::<Vec<_>>();
+                adjacent.sort_unstable();
+                adjacent.dedup();
+                for &s in &adjacent {
+                    symbols[s].numbers.push(numbers.len());
+                }
+                numbers.push(Number {
+                    value,
+                    row,
+                    columns: start..col,
+                    symbols: adjacent,
+                });
             }
+        }
+
+        Ok(Schematic { numbers, symbols })
+    }
+
+    /// Numbers adjacent to at least one symbol.
+    fn part_numbers(&self) -> impl Iterator<Item = &Number> {
+        self.numbers.iter().filter(|n| !n.symbols.is_empty())
+    }
+
+    /// Sum of the part numbers, or `None` if it overflows.
+    fn part_total(&self) -> Option<u64> {
+        self.part_numbers()
+            .try_fold(0u64, |acc, n| acc.checked_add(n.value))
+    }
+
+    /// Values of every symbol that satisfies the first rule whose class it belongs to,
+    /// `None` for the ones that overflow.
+    fn gear_values<'a>(&'a self, rules: &'a [GearRule]) -> impl Iterator<Item = Option<u64>> + 'a {
+        self.symbols.iter().filter_map(|s| {
+            let rule = rules.iter().find(|r| r.glyphs.contains(s.glyph))?;
+            rule.requirement.is_met_by(s.numbers.len()).then(|| {
+                let values = s.numbers.iter().map(|&n| self.numbers[n].value);
+                rule.aggregation.apply(values)
+            })
         })
-        .sum()
-}
+    }
+
+    fn gear_total(&self, rules: &[GearRule]) -> Option<u64> {
+        self.gear_values(rules)
+            .try_fold(0u64, |acc, v| acc.checked_add(v?))
+    }
 
-fn number_at(nbor: GridIndex, grid: &Grid<char>, visited: &mut Grid<bool>) -> u32 {
-    visited[nbor] = true;
-    let mut start = nbor;
-    let mut end = nbor;
-    while let Some(i) = grid.left_index(start).and_then(|i| {
-        visited[i] = true;
-        grid[i].is_ascii_digit().then_some(i)
-    }) {
-        start = i;
+    /// Render the graph in Graphviz DOT format.
+    fn to_dot(&self) -> String {
+        let mut out = String::from("graph schematic {\n");
+        for (i, n) in self.numbers.iter().enumerate() {
+            writeln!(out, "    n{i} [label=\"{}\"];", n.value).unwrap();
+        }
+        for (i, s) in self.symbols.iter().enumerate() {
+            let label = escape(s.glyph);
+            writeln!(out, "    s{i} [label=\"{label}\", shape=box];").unwrap();
+        }
+        for (i, n) in self.numbers.iter().enumerate() {
+            for s in &n.symbols {
+                writeln!(out, "    n{i} -- s{s};").unwrap();
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Render the graph as a JSON object with `numbers` and `symbols` arrays,
+    /// cross-referencing each other by index.
+    fn to_json(&self) -> String {
+        let numbers = self
+            .numbers
+            .iter()
+            .map(|n| {
+                format!(
+                    r#"{{"value":{},"row":{},"columns":[{},{}],"symbols":{:?}}}"#,
+                    n.value, n.row, n.columns.start, n.columns.end, n.symbols
+                )
+            })
+            .collect::<Vec<_>>();
+        let symbols = self
+            .symbols
+            .iter()
+            .map(|s| {
+                format!(
+                    r#"{{"symbol":"{}","row":{},"column":{},"numbers":{:?}}}"#,
+                    escape(s.glyph),
+                    s.position.row(),
+                    s.position.column(),
+                    s.numbers
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            r#"{{"numbers":[{}],"symbols":[{}]}}"#,
+            numbers.join(","),
+            symbols.join(",")
+        )
     }
-    while let Some(i) = grid.right_index(end).and_then(|i| {
-        visited[i] = true;
-        grid[i].is_ascii_digit().then_some(i)
-    }) {
-        end = i;
+}
+
+/// Escape a symbol for use inside a double-quoted DOT or JSON string.
+fn escape(c: char) -> String {
+    match c {
+        '"' | '\\' => format!("\\{c}"),
+        _ => c.to_string(),
     }
-    let (col, row) = (start.column(), start.row());
-    (0..=end.column() - start.column()).fold(0, |num, i| {
-        10 * num + grid[(col + i, row)].to_digit(10).unwrap()
-    })
 }
 
 #[cfg(test)]
@@ -93,6 +396,103 @@ mod tests {
         println!("{}", grid.to_pretty_string());
     }
 
+    #[test]
+    fn can_build_schematic() {
+        let schematic = Schematic::parse(INPUT);
+        assert_eq!(schematic.numbers.len(), 10);
+        assert_eq!(schematic.symbols.len(), 6);
+        assert_eq!(
+            schematic.numbers[0],
+            Number {
+                value: 467,
+                row: 0,
+                columns: 0..3,
+                symbols: vec![0],
+            }
+        );
+        let star = &schematic.symbols[0];
+        assert_eq!((star.glyph, star.position), ('*', GridIndex::new(3, 1)));
+        let values = star.numbers.iter().map(|&n| schematic.numbers[n].value);
+        assert_eq!(values.collect::<Vec<_>>(), [467, 35]);
+        let unattached = schematic.numbers.iter().filter(|n| n.symbols.is_empty());
+        assert_eq!(unattached.map(|n| n.value).collect::<Vec<_>>(), [114, 58]);
+    }
+
+    #[test]
+    fn can_export_schematic() {
+        let schematic = Schematic::parse("1.\n.\"\n");
+        assert_eq!(
+            schematic.to_dot(),
+            "graph schematic {\n    n0 [label=\"1\"];\n    s0 [label=\"\\\"\", shape=box];\n    n0 -- s0;\n}\n"
+        );
+        assert_eq!(
+            schematic.to_json(),
+            r#"{"numbers":[{"value":1,"row":0,"columns":[0,1],"symbols":[0]}],"symbols":[{"symbol":"\"","row":1,"column":1,"numbers":[0]}]}"#
+        );
+    }
+
+    #[test]
+    fn can_parse_gear_rules() {
+        assert_eq!(
+            "#$:1+:sum".parse(),
+            Ok(GearRule {
+                glyphs: "#$".to_owned(),
+                requirement: Requirement::AtLeast(1),
+                aggregation: Aggregation::Sum,
+            })
+        );
+        assert_eq!("::2:max".parse::<GearRule>().unwrap().glyphs, ":");
+        assert!("*:two:product".parse::<GearRule>().is_err());
+        assert!("*:2".parse::<GearRule>().is_err());
+    }
+
+    #[test]
+    fn can_apply_custom_rules() {
+        let rules = RuleSet {
+            adjacency: Adjacency::Eight,
+            rules: vec!["*:1+:max".parse().unwrap(), "#+$:1:sum".parse().unwrap()],
+        };
+        assert_eq!(
+            rules.evaluate(INPUT),
+            Some(467 + 617 + 755 + 633 + 592 + 664)
+        );
+
+        let four = RuleSet {
+            adjacency: Adjacency::Four,
+            ..RuleSet::part2()
+        };
+        assert_eq!(four.evaluate(INPUT), Some(0));
+        let schematic = Schematic::parse_with(INPUT, Adjacency::Four).unwrap();
+        let parts = schematic.part_numbers().map(|n| n.value);
+        assert_eq!(parts.collect::<Vec<_>>(), [35, 633, 617, 664, 598]);
+    }
+
+    #[test]
+    fn detects_overflow() {
+        // the `*` touches six numbers, whose product doesn't fit into u64
+        let input = "999999.999999\n999999*999999\n999999.999999";
+        let rules = |rule: &str| RuleSet {
+            adjacency: Adjacency::Eight,
+            rules: vec![rule.parse().unwrap()],
+        };
+        assert_eq!(rules("*:1+:product").evaluate(input), None);
+        assert_eq!(rules("*:6:sum").evaluate(input), Some(6 * 999999));
+        assert_eq!(rules("*:6:max").evaluate(input), Some(999999));
+
+        // numbers may have more digits than fit into u32, but not more than fit into u64
+        let long = "12345678901*";
+        assert_eq!(rules("*:1:sum").evaluate(long), Some(12345678901));
+        assert_eq!(Schematic::parse(long).part_total(), Some(12345678901));
+        let too_long = "*123456789012345678901";
+        assert_eq!(rules("*:1+:sum").evaluate(too_long), None);
+        assert_eq!(
+            Schematic::parse_with(too_long, Adjacency::Eight),
+            Err("the number at row 0, column 1 doesn't fit into u64".to_owned())
+        );
+        let parts = "18446744073709551615*1";
+        assert_eq!(Schematic::parse(parts).part_total(), None);
+    }
+
     #[test]
     fn can_solve_part1() {
         assert_eq!(solve_part1(INPUT), 4361);
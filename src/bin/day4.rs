@@ -1,7 +1,47 @@
+use itertools::Itertools;
+use num_bigint::BigUint;
+use num_traits::{CheckedAdd, One, Zero};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Write};
+use std::ops::Bound::{Excluded, Included};
+
 fn main() {
     let input = std::fs::read_to_string("inputs/day4").unwrap();
-    println!("Part 1: {}", solve_part1(input.trim()));
-    println!("Part 2: {}", solve_part2(input.trim()));
+    match std::env::args().nth(1).as_deref() {
+        Some("--big") => println!("Part 2: {}", solve_part2_big(input.trim())),
+        Some("--trace") => {
+            let matches = match_counts(input.trim());
+            let cards = trace::<BigUint>(&matches).expect("big integers don't overflow");
+            print!("{}", trace_table(&cards));
+        }
+        Some(flag @ ("--strict" | "--lenient")) => {
+            let mode = if flag == "--strict" {
+                Mode::Strict
+            } else {
+                Mode::Lenient
+            };
+            match parse_cards(&input, mode) {
+                Ok((cards, warnings)) => {
+                    for w in warnings {
+                        eprintln!("warning: {w}");
+                    }
+                    let matches: Vec<_> = cards.iter().map(Card::matches).collect();
+                    println!("Part 1: {}", points(&matches));
+                    println!("Part 2: {}", total_copies_by_id::<BigUint>(&cards).unwrap());
+                }
+                Err(errors) => {
+                    for e in errors {
+                        eprintln!("error: {e}");
+                    }
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => {
+            println!("Part 1: {}", solve_part1(input.trim()));
+            println!("Part 2: {}", solve_part2(input.trim()));
+        }
+    }
 }
 
 fn parse_line(s: &str) -> (Vec<i32>, Vec<i32>) {
@@ -27,27 +67,307 @@ fn count_matches(winning_numbers: &[i32], given_numbers: &[i32]) -> usize {
         .count()
 }
 
-fn solve_part1(input: &str) -> usize {
+fn match_counts(input: &str) -> Vec<usize> {
     input
         .lines()
         .map(|s| {
             let (l, r) = parse_line(s);
-            1 << count_matches(&l, &r) >> 1
+            count_matches(&l, &r)
         })
-        .sum()
+        .collect()
+}
+
+fn points(matches: &[usize]) -> usize {
+    matches.iter().map(|m| 1 << m >> 1).sum()
+}
+
+fn solve_part1(input: &str) -> usize {
+    points(&match_counts(input))
 }
 
 fn solve_part2(input: &str) -> usize {
-    let scratchpads: Vec<_> = input.trim().lines().map(parse_line).collect();
-    let mut counts = vec![1; scratchpads.len()];
-    for (i, (l, r)) in scratchpads.iter().enumerate() {
-        let num_matches = count_matches(l, r);
-        let amount = counts[i];
-        for count in &mut counts[i + 1..][..num_matches] {
-            *count += amount;
+    total_copies(&match_counts(input))
+        .expect("card count overflows usize, use the big integer mode")
+}
+
+fn solve_part2_big(input: &str) -> BigUint {
+    total_copies(&match_counts(input)).expect("big integers don't overflow")
+}
+
+/// Total number of scratchcards, or `None` if it doesn't fit into `T`.
+/// Matches reaching past the last card win nothing.
+fn total_copies<T: Clone + Zero + One + CheckedAdd>(matches: &[usize]) -> Option<T> {
+    let mut counts = vec![T::one(); matches.len()];
+    for (i, &num_matches) in matches.iter().enumerate() {
+        let amount = counts[i].clone();
+        let end = (i + 1 + num_matches).min(counts.len());
+        for count in &mut counts[i + 1..end] {
+            *count = count.checked_add(&amount)?;
+        }
+    }
+    counts
+        .into_iter()
+        .try_fold(T::zero(), |acc, c| acc.checked_add(&c))
+}
+
+/// Like [`total_copies`], but every card wins copies of the cards whose ids follow its own,
+/// so a card missing from the input wins nothing instead of shifting the copies onto later cards.
+/// Only the first card with a given id counts, like [`parse_cards`] keeps only that one.
+fn total_copies_by_id<T: Clone + Zero + One + CheckedAdd>(cards: &[Card]) -> Option<T> {
+    let mut counts: BTreeMap<usize, T> = cards.iter().map(|c| (c.id, T::one())).collect();
+    let mut cards: Vec<&Card> = cards.iter().collect();
+    cards.sort_by_key(|c| c.id);
+    cards.dedup_by_key(|c| c.id);
+    for card in cards {
+        let amount = counts[&card.id].clone();
+        let won = (Excluded(card.id), Included(card.id + card.matches()));
+        for (_, count) in counts.range_mut(won) {
+            *count = count.checked_add(&amount)?;
+        }
+    }
+    counts
+        .into_values()
+        .try_fold(T::zero(), |acc, c| acc.checked_add(&c))
+}
+
+/// How a single card ended up with its number of copies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CardTrace<T> {
+    copies: T,
+    /// `(card index, copies)` won from each earlier card
+    received: Vec<(usize, T)>,
+}
+
+/// Like [`total_copies`], but records where every card's copies came from.
+/// Returns `None` if a count doesn't fit into `T`.
+fn trace<T: Clone + One + CheckedAdd>(matches: &[usize]) -> Option<Vec<CardTrace<T>>> {
+    let mut cards = vec![
+        CardTrace {
+            copies: T::one(),
+            received: Vec::new(),
+        };
+        matches.len()
+    ];
+    for (i, &num_matches) in matches.iter().enumerate() {
+        let amount = cards[i].copies.clone();
+        let end = (i + 1 + num_matches).min(cards.len());
+        for card in &mut cards[i + 1..end] {
+            card.copies = card.copies.checked_add(&amount)?;
+            card.received.push((i, amount.clone()));
+        }
+    }
+    Some(cards)
+}
+
+/// Tab separated table with one row per card. Sources are listed as `card:copies`.
+fn trace_table<T: Display>(cards: &[CardTrace<T>]) -> String {
+    let mut table = String::from("card\tcopies\treceived from\n");
+    for (i, card) in cards.iter().enumerate() {
+        let sources = card
+            .received
+            .iter()
+            .map(|(j, n)| format!("{}:{n}", j + 1))
+            .collect::<Vec<_>>();
+        writeln!(table, "{}\t{}\t{}", i + 1, card.copies, sources.join(" ")).unwrap();
+    }
+    table
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Card {
+    id: usize,
+    winning: Vec<i32>,
+    given: Vec<i32>,
+}
+
+impl Card {
+    fn matches(&self) -> usize {
+        count_matches(&self.winning, &self.given)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Any diagnostic is an error.
+    Strict,
+    /// Recover where possible and report the diagnostics as warnings.
+    Lenient,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum List {
+    Winning,
+    Given,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Problem {
+    /// The line doesn't start with `Card <id>:`. The line is skipped.
+    BadHeader,
+    /// The `|` between the winning and the given numbers is missing. The line is skipped.
+    MissingBar,
+    /// The card id doesn't follow the previous one.
+    IdGap { expected: usize, found: usize },
+    /// An earlier card has the same id. The line is skipped, keeping the first card.
+    DuplicateId(usize),
+    /// A winning number is listed more than once. The repetition is dropped.
+    DuplicateWinning(i32),
+    /// A token in either list isn't a number. The token is dropped.
+    NotANumber(String),
+    /// A list has a different length than on the first card.
+    LengthMismatch {
+        list: List,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::BadHeader => write!(f, "expected `Card <id>:`"),
+            Problem::MissingBar => write!(f, "missing `|` separator"),
+            Problem::IdGap { expected, found } => {
+                write!(f, "expected card {expected}, found card {found}")
+            }
+            Problem::DuplicateId(id) => write!(f, "card {id} is listed more than once"),
+            Problem::DuplicateWinning(n) => write!(f, "duplicated winning number {n}"),
+            Problem::NotANumber(s) => write!(f, "{s:?} is not a number"),
+            Problem::LengthMismatch {
+                list,
+                expected,
+                found,
+            } => write!(f, "expected {expected} {list:?} numbers, found {found}"),
         }
     }
-    counts.into_iter().sum()
+}
+
+/// A problem found by [`parse_cards`]. Lines and columns are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Diagnostic {
+    line: usize,
+    column: usize,
+    problem: Problem,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.problem)
+    }
+}
+
+/// Whitespace separated tokens of `s`, with their byte offset into `line`.
+fn tokens<'a>(line: &'a str, s: &'a str) -> impl Iterator<Item = (usize, &'a str)> {
+    s.split_ascii_whitespace()
+        .map(move |t| (t.as_ptr() as usize - line.as_ptr() as usize, t))
+}
+
+/// Validating alternative to [`parse_line`]. Blank lines are ignored.
+/// In [`Mode::Strict`] all diagnostics are returned as errors, otherwise they're
+/// returned alongside the cards that could be recovered.
+fn parse_cards(input: &str, mode: Mode) -> Result<(Vec<Card>, Vec<Diagnostic>), Vec<Diagnostic>> {
+    let mut cards: Vec<Card> = Vec::new();
+    let mut diagnostics = Vec::new();
+    for (line_idx, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut report = |offset: usize, problem| {
+            diagnostics.push(Diagnostic {
+                line: line_idx + 1,
+                column: offset + 1,
+                problem,
+            })
+        };
+
+        let header = line.split_once(':').and_then(|(header, _)| {
+            let (offset, id) = tokens(line, header)
+                .collect_tuple()
+                .filter(|&((_, card), _)| card == "Card")?
+                .1;
+            Some((offset, id.parse::<usize>().ok()?))
+        });
+        let Some((id_offset, id)) = header else {
+            report(0, Problem::BadHeader);
+            continue;
+        };
+        let (_, numbers) = line.split_once(':').unwrap();
+        let Some((left, right)) = numbers.split_once('|') else {
+            report(line.len(), Problem::MissingBar);
+            continue;
+        };
+
+        if cards.iter().any(|c| c.id == id) {
+            report(id_offset, Problem::DuplicateId(id));
+            continue;
+        }
+        if let Some(prev) = cards.last() {
+            if id != prev.id + 1 {
+                let expected = prev.id + 1;
+                report(
+                    id_offset,
+                    Problem::IdGap {
+                        expected,
+                        found: id,
+                    },
+                );
+            }
+        }
+
+        let parse_list = |s: &str, report: &mut dyn FnMut(usize, Problem)| {
+            let mut numbers = Vec::new();
+            for (offset, token) in tokens(line, s) {
+                match token.parse() {
+                    Ok(n) => numbers.push((offset, n)),
+                    Err(_) => report(offset, Problem::NotANumber(token.to_owned())),
+                }
+            }
+            numbers
+        };
+        let winning_with_offsets = parse_list(left, &mut report);
+        let given = parse_list(right, &mut report);
+
+        let mut winning: Vec<i32> = Vec::new();
+        for (offset, n) in winning_with_offsets {
+            if winning.contains(&n) {
+                report(offset, Problem::DuplicateWinning(n));
+            } else {
+                winning.push(n);
+            }
+        }
+
+        if let Some(first) = cards.first() {
+            let lists = [
+                (List::Winning, left, first.winning.len(), winning.len()),
+                (List::Given, right, first.given.len(), given.len()),
+            ];
+            for (list, s, expected, found) in lists {
+                if expected != found {
+                    let offset = s.trim_start().as_ptr() as usize - line.as_ptr() as usize;
+                    report(
+                        offset,
+                        Problem::LengthMismatch {
+                            list,
+                            expected,
+                            found,
+                        },
+                    );
+                }
+            }
+        }
+
+        cards.push(Card {
+            id,
+            winning,
+            given: given.into_iter().map(|(_, n)| n).collect(),
+        });
+    }
+
+    if mode == Mode::Strict && !diagnostics.is_empty() {
+        Err(diagnostics)
+    } else {
+        Ok((cards, diagnostics))
+    }
 }
 
 #[cfg(test)]
@@ -71,6 +391,90 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
         }
     }
 
+    #[test]
+    fn validates_cards() {
+        let (cards, diagnostics) = parse_cards(INPUT, Mode::Strict).unwrap();
+        assert!(diagnostics.is_empty());
+        let matches: Vec<_> = cards.iter().map(Card::matches).collect();
+        assert_eq!(matches, match_counts(INPUT));
+
+        let input = "\
+Card 1: 41 48 | 83 86  6
+Card 3: 13 x3 13 | 61 30 68
+
+Card 4: 1 2 3 4 5
+Game 5: 1 2 | 3 4 5
+Card 6: 7 8 | 9 10";
+        let error = |line, column, problem| Diagnostic {
+            line,
+            column,
+            problem,
+        };
+        let expected = vec![
+            error(
+                2,
+                6,
+                Problem::IdGap {
+                    expected: 2,
+                    found: 3,
+                },
+            ),
+            error(2, 12, Problem::NotANumber("x3".to_owned())),
+            error(2, 15, Problem::DuplicateWinning(13)),
+            error(
+                2,
+                9,
+                Problem::LengthMismatch {
+                    list: List::Winning,
+                    expected: 2,
+                    found: 1,
+                },
+            ),
+            error(4, 18, Problem::MissingBar),
+            error(5, 1, Problem::BadHeader),
+            error(
+                6,
+                6,
+                Problem::IdGap {
+                    expected: 4,
+                    found: 6,
+                },
+            ),
+            error(
+                6,
+                15,
+                Problem::LengthMismatch {
+                    list: List::Given,
+                    expected: 3,
+                    found: 2,
+                },
+            ),
+        ];
+        assert_eq!(parse_cards(input, Mode::Strict), Err(expected.clone()));
+
+        let (cards, diagnostics) = parse_cards(input, Mode::Lenient).unwrap();
+        assert_eq!(diagnostics, expected);
+        assert_eq!(cards.iter().map(|c| c.id).collect::<Vec<_>>(), [1, 3, 6]);
+        assert_eq!(cards[1].winning, [13]);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "2:6: expected card 2, found card 3"
+        );
+
+        // a repeated card isn't a gap, and only the first one is kept
+        let input = "Card 1: 1 | 1\nCard 1: 2 | 3\nCard 2: 4 | 5";
+        let expected = vec![error(2, 6, Problem::DuplicateId(1))];
+        assert_eq!(parse_cards(input, Mode::Strict), Err(expected.clone()));
+        let (cards, diagnostics) = parse_cards(input, Mode::Lenient).unwrap();
+        assert_eq!(diagnostics, expected);
+        assert_eq!(cards.iter().map(|c| c.id).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(cards[0].winning, [1]);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "2:6: card 1 is listed more than once"
+        );
+    }
+
     #[test]
     fn count_wins() {
         let expected = [4, 2, 2, 1, 0, 0];
@@ -88,5 +492,76 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
     #[test]
     fn solves_part2() {
         assert_eq!(solve_part2(INPUT), 30);
+        assert_eq!(solve_part2_big(INPUT), BigUint::from(30u32));
+    }
+
+    #[test]
+    fn detects_overflow() {
+        // every card wins all of the following ones, doubling the number of copies each time
+        let cascade = |n: usize| {
+            (0..n)
+                .map(|i| format!("Card {}: 1 | {}", i + 1, "1 ".repeat(n - 1 - i)))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        assert_eq!(total_copies::<u8>(&match_counts(&cascade(8))), Some(255));
+        assert_eq!(total_copies::<u8>(&match_counts(&cascade(9))), None);
+        assert_eq!(solve_part2_big(&cascade(9)), BigUint::from(511u32));
+        assert_eq!(
+            solve_part2_big(&cascade(100)),
+            (BigUint::one() << 100) - BigUint::one()
+        );
+        assert!(trace::<u8>(&match_counts(&cascade(9))).is_none());
+
+        // the last card's matches run past the end of the table
+        let overshoot = "Card 1: 1 | 1\nCard 2: 1 2 | 1 2";
+        assert_eq!(total_copies::<u8>(&match_counts(overshoot)), Some(3));
+        assert_eq!(trace::<u8>(&match_counts(overshoot)).unwrap()[1].copies, 2);
+    }
+
+    #[test]
+    fn counts_copies_by_id() {
+        // card 2 is missing, so card 1's two matches only win a copy of card 3,
+        // and card 3's match would be card 4, which doesn't exist
+        let input = "Card 1: 1 2 | 1 2\nCard 3: 5 6 | 5 7";
+        let (cards, warnings) = parse_cards(input, Mode::Lenient).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            total_copies_by_id::<BigUint>(&cards),
+            Some(BigUint::from(3u32))
+        );
+
+        let input = "Card 1: 1 2 | 3 4\nCard 3: 5 6 | 5 7";
+        let (cards, _) = parse_cards(input, Mode::Lenient).unwrap();
+        assert_eq!(
+            total_copies_by_id::<BigUint>(&cards),
+            Some(BigUint::from(2u32))
+        );
+
+        let (cards, _) = parse_cards(INPUT, Mode::Strict).unwrap();
+        assert_eq!(total_copies_by_id::<usize>(&cards), Some(30));
+
+        // the repeated card 1 is dropped, so card 2 only gets one extra copy
+        let input = "Card 1: 1 | 1\nCard 1: 1 | 1\nCard 2: 4 | 5";
+        let (cards, warnings) = parse_cards(input, Mode::Lenient).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(total_copies_by_id::<usize>(&cards), Some(3));
+        let repeated = [cards[0].clone(), cards[0].clone(), cards[1].clone()];
+        assert_eq!(total_copies_by_id::<usize>(&repeated), Some(3));
+    }
+
+    #[test]
+    fn traces_copies() {
+        let cards = trace::<usize>(&match_counts(INPUT)).unwrap();
+        assert_eq!(
+            cards[3],
+            CardTrace {
+                copies: 8,
+                received: vec![(0, 1), (1, 2), (2, 4)],
+            }
+        );
+        let table = trace_table(&cards);
+        assert_eq!(table.lines().count(), 7);
+        assert_eq!(table.lines().nth(4), Some("4\t8\t1:1 2:2 3:4"));
     }
 }
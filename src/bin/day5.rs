@@ -1,38 +1,196 @@
 use itertools::Itertools;
+use std::fmt::{self, Display};
 use std::ops::Range;
 
 fn main() {
     let input = std::fs::read_to_string("inputs/day5").unwrap();
-    println!("Part 1: {}", solve_part1(input.trim()));
-    println!("Part 2: {}", solve_part2(input.trim()));
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("--seed-for") => {
+            let location = args.next().expect("location").parse().expect("number");
+            let (_, almanac) = parse_almanac(input.trim());
+            let map = almanac
+                .resolve("seed", "location")
+                .expect("path from seed to location")
+                .inverse()
+                .expect("maps must be bijections");
+            println!("Seed: {}", map.transform(location));
+        }
+        Some("--query") => {
+            let (src, dst) = (args.next().expect("source"), args.next().expect("target"));
+            let (seeds, almanac) = parse_almanac(input.trim());
+            let ranges = seeds.iter().tuples().map(|(&s, &n)| s..s + n).collect_vec();
+            let (points, ranges) = match (
+                almanac.query(&src, &dst, &seeds),
+                almanac.query_ranges(&src, &dst, &ranges),
+            ) {
+                (Ok(points), Ok(ranges)) => (points, ranges),
+                (Err(e), _) | (_, Err(e)) => {
+                    println!("{e}");
+                    std::process::exit(1);
+                }
+            };
+            let show = |x: Option<i64>| x.map_or("none".to_owned(), |x| x.to_string());
+            println!("Lowest {dst} (points): {}", show(points.into_iter().min()));
+            println!("Lowest {dst} (ranges): {}", show(lowest(&ranges)));
+        }
+        Some("--validate") => {
+            let root = args.next().unwrap_or_else(|| "seed".to_owned());
+            let diagnostics = validate_almanac(&input, &root);
+            for d in &diagnostics {
+                println!("{d}");
+            }
+            if !diagnostics.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            let show = |x: Option<i64>| x.map_or("no seeds".to_owned(), |x| x.to_string());
+            println!("Part 1: {}", show(solve_part1(input.trim())));
+            println!("Part 2: {}", show(solve_part2(input.trim())));
+        }
+    }
 }
 
-#[allow(clippy::single_range_in_vec_init)]
-fn solve_part2(input: &str) -> i64 {
-    let (header, rest) = input.split_once("\n\n").expect("seeds");
-    let maps = rest.split("\n\n").map(parse_map).collect_vec();
-    parse_seeds(header)
+/// The lowest location of any seed range, or `None` if they're all empty.
+fn solve_part2(input: &str) -> Option<i64> {
+    let (seeds, almanac) = parse_almanac(input);
+    let ranges = seeds
+        .into_iter()
         .tuples()
-        .flat_map(|(start, len)| {
-            maps.iter().fold(vec![start..start + len], |rs, map| {
-                rs.iter()
-                    .flat_map(|r| map.transform_range(r.clone()))
-                    .collect_vec()
-            })
-        })
+        .map(|(s, n)| s..s + n)
+        .collect_vec();
+    let locations = almanac
+        .query_ranges("seed", "location", &ranges)
+        .expect("path from seed to location");
+    lowest(&locations)
+}
+
+/// The lowest location of any seed, or `None` if there are no seeds.
+fn solve_part1(input: &str) -> Option<i64> {
+    let (seeds, almanac) = parse_almanac(input);
+    almanac
+        .query("seed", "location", &seeds)
+        .expect("path from seed to location")
+        .into_iter()
+        .min()
+}
+
+fn lowest(ranges: &[Range<i64>]) -> Option<i64> {
+    ranges
+        .iter()
         .filter(|r| !r.is_empty())
         .map(|r| r.start)
         .min()
-        .unwrap()
 }
 
-fn solve_part1(input: &str) -> i64 {
+fn parse_almanac(input: &str) -> (Vec<i64>, Almanac) {
     let (header, rest) = input.split_once("\n\n").expect("seeds");
     let maps = rest.split("\n\n").map(parse_map).collect_vec();
-    parse_seeds(header)
-        .map(|seed| maps.iter().fold(seed, |s, map| map.transform(s)))
-        .min()
-        .unwrap()
+    (parse_seeds(header).collect(), Almanac { maps })
+}
+
+/// The almanac's maps, connected into a graph by the categories in their `a-to-b` labels.
+/// The maps may come in any order, and any category may have several outgoing maps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Almanac {
+    maps: Vec<Map>,
+}
+
+/// Why [`Almanac::resolve`] can't convert between two categories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum QueryError {
+    /// No chain of maps leads from the source to the target category.
+    NoPath { src: String, dst: String },
+    /// Several chains lead there and convert some values differently. Each is listed by its maps' labels.
+    Ambiguous(Vec<Vec<String>>),
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::NoPath { src, dst } => {
+                write!(f, "no chain of maps leads from {src} to {dst}")
+            }
+            QueryError::Ambiguous(paths) => {
+                let paths = paths.iter().map(|p| p.join(", "));
+                write!(f, "chains disagree: {}", paths.format(" / "))
+            }
+        }
+    }
+}
+
+impl Almanac {
+    /// Every chain of maps leading from the `src` to the `dst` category without visiting a category twice.
+    fn paths(&self, src: &str, dst: &str) -> Vec<Vec<&Map>> {
+        let mut paths = Vec::new();
+        let mut todo = vec![(src, Vec::new())];
+        while let Some((category, path)) = todo.pop() {
+            if category == dst {
+                paths.push(path);
+                continue;
+            }
+            for map in &self.maps {
+                let Some((from, to)) = map.categories() else {
+                    continue;
+                };
+                let visited =
+                    to == src || path.iter().any(|m: &&Map| m.categories().unwrap().1 == to);
+                if from == category && !visited {
+                    let mut path = path.clone();
+                    path.push(map);
+                    todo.push((to, path));
+                }
+            }
+        }
+        paths
+    }
+
+    /// A single map converting `src` values to `dst` values. When several chains of maps
+    /// lead from `src` to `dst`, they must all convert every value the same way.
+    fn resolve(&self, src: &str, dst: &str) -> Result<Map, QueryError> {
+        let paths = self.paths(src, dst);
+        let maps = paths
+            .iter()
+            .map(|p| compose_all(p.iter().copied()))
+            .collect_vec();
+        let Some(first) = maps.first() else {
+            return Err(QueryError::NoPath {
+                src: src.to_owned(),
+                dst: dst.to_owned(),
+            });
+        };
+        if maps.iter().all(|m| m.mappings == first.mappings) {
+            return Ok(first.clone());
+        }
+        let labels = paths
+            .iter()
+            .map(|p| p.iter().map(|m| m.label.clone()).collect_vec())
+            .sorted()
+            .collect();
+        Err(QueryError::Ambiguous(labels))
+    }
+
+    fn query(&self, src: &str, dst: &str, points: &[i64]) -> Result<Vec<i64>, QueryError> {
+        let map = self.resolve(src, dst)?;
+        Ok(points.iter().map(|&x| map.transform(x)).collect())
+    }
+
+    /// The `dst` values of all the `src` values in `ranges`, as sorted disjoint ranges.
+    fn query_ranges(
+        &self,
+        src: &str,
+        dst: &str,
+        ranges: &[Range<i64>],
+    ) -> Result<Vec<Range<i64>>, QueryError> {
+        let map = self.resolve(src, dst)?;
+        let mut result = ranges
+            .iter()
+            .flat_map(|r| map.transform_range(r.clone()))
+            .collect_vec();
+        result.sort_unstable_by_key(|r| r.start);
+        Ok(merge_ranges(result.into_iter()))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -47,45 +205,127 @@ struct Map {
     mappings: Vec<Mapping>,
 }
 
+/// Compose the maps in order into a single one, so that any query takes a single lookup.
+fn compose_all<'a>(maps: impl IntoIterator<Item = &'a Map>) -> Map {
+    maps.into_iter()
+        .fold(Map::identity(), |acc, map| acc.then(map))
+}
+
 impl Map {
-    fn transform(&self, i: i64) -> i64 {
-        match find_map_item(&self.mappings, i) {
-            Some(m) => i + m.offset,
-            None => i,
+    /// The map that leaves every value unchanged. It's neutral with respect to [`Map::then`].
+    fn identity() -> Self {
+        Map {
+            label: String::new(),
+            mappings: Vec::new(),
         }
     }
 
-    fn transform_range(&self, r: Range<i64>) -> Vec<Range<i64>> {
-        let ms = &self.mappings;
-        let mut start = r.start;
+    /// The source and destination categories, if the label is of the form `a-to-b`.
+    fn categories(&self) -> Option<(&str, &str)> {
+        self.label.split_once("-to-")
+    }
 
-        let Some(a) = search_sorted_mappings(ms, start) else {
-            return vec![r];
+    /// The map equivalent to applying `self` and then `next`.
+    fn then(&self, next: &Map) -> Map {
+        let label = match (self.categories(), next.categories()) {
+            (Some((src, _)), Some((_, dst))) => format!("{src}-to-{dst}"),
+            (None, _) => next.label.clone(),
+            (_, None) => self.label.clone(),
+        };
+        // outside of the hull of all the ranges both maps are the identity
+        let ranges = self.mappings.iter().chain(&next.mappings).map(|m| &m.range);
+        let (Some(lo), Some(hi)) = (
+            ranges.clone().map(|r| r.start).min(),
+            ranges.map(|r| r.end).max(),
+        ) else {
+            return Map {
+                label,
+                ..Map::identity()
+            };
         };
 
+        let mut mappings: Vec<Mapping> = Vec::new();
+        for (piece, a) in self.segments(lo..hi) {
+            for (image, b) in next.segments(piece.start + a..piece.end + a) {
+                let range = image.start - a..image.end - a;
+                let offset = a + b;
+                match mappings.last_mut() {
+                    Some(m) if m.range.end == range.start && m.offset == offset => {
+                        m.range.end = range.end
+                    }
+                    _ if offset == 0 => {}
+                    _ => mappings.push(Mapping { range, offset }),
+                }
+            }
+        }
+        Map { label, mappings }
+    }
+
+    /// The inverse map, or `None` if `self` isn't a bijection,
+    /// i.e. its mappings don't just permute the values of their source ranges.
+    fn inverse(&self) -> Option<Map> {
+        let mut mappings = self
+            .mappings
+            .iter()
+            .map(|m| Mapping {
+                range: m.range.start + m.offset..m.range.end + m.offset,
+                offset: -m.offset,
+            })
+            .collect_vec();
+        mappings.sort_unstable_by_key(|m| m.range.start);
+        let disjoint = mappings
+            .windows(2)
+            .all(|w| w[0].range.end <= w[1].range.start);
+        let sources = merge_ranges(self.mappings.iter().map(|m| m.range.clone()));
+        let images = merge_ranges(mappings.iter().map(|m| m.range.clone()));
+        let label = match self.categories() {
+            Some((src, dst)) => format!("{dst}-to-{src}"),
+            None => self.label.clone(),
+        };
+        (disjoint && sources == images).then_some(Map { label, mappings })
+    }
+
+    /// Split `r` into consecutive pieces, each paired with the offset applied to it
+    /// (`0` for the parts not covered by any mapping).
+    fn segments(&self, r: Range<i64>) -> Vec<(Range<i64>, i64)> {
+        let ms = &self.mappings;
+        let mut start = r.start;
         let mut result = Vec::new();
-        for m in &ms[a..] {
+        for m in &ms[ms.partition_point(|m| m.range.end <= start)..] {
+            if start >= r.end {
+                break;
+            }
             if start < m.range.start {
-                if r.end <= m.range.start {
-                    result.push(start..r.end);
-                    return result;
+                let end = m.range.start.min(r.end);
+                result.push((start..end, 0));
+                start = end;
+                if start >= r.end {
+                    break;
                 }
-                result.push(start..m.range.start);
-                start = m.range.start;
             }
             let end = m.range.end.min(r.end);
-            result.push(start + m.offset..end + m.offset);
+            result.push((start..end, m.offset));
             start = end;
-            if start >= r.end {
-                break;
-            }
         }
-        if start <= r.end {
-            result.push(start..r.end);
+        if start < r.end {
+            result.push((start..r.end, 0));
         }
-
         result
     }
+
+    fn transform(&self, i: i64) -> i64 {
+        match find_map_item(&self.mappings, i) {
+            Some(m) => i + m.offset,
+            None => i,
+        }
+    }
+
+    fn transform_range(&self, r: Range<i64>) -> Vec<Range<i64>> {
+        self.segments(r)
+            .into_iter()
+            .map(|(r, offset)| r.start + offset..r.end + offset)
+            .collect()
+    }
 }
 
 fn find_map_item(items: &[Mapping], x: i64) -> Option<Mapping> {
@@ -98,6 +338,18 @@ fn find_map_item(items: &[Mapping], x: i64) -> Option<Mapping> {
     })
 }
 
+/// Merge sorted ranges into the minimal list of disjoint ones covering the same values.
+fn merge_ranges(ranges: impl Iterator<Item = Range<i64>>) -> Vec<Range<i64>> {
+    let mut merged: Vec<Range<i64>> = Vec::new();
+    for r in ranges.filter(|r| !r.is_empty()) {
+        match merged.last_mut() {
+            Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+            _ => merged.push(r),
+        }
+    }
+    merged
+}
+
 fn parse_seeds(seeds: &str) -> impl Iterator<Item = i64> + '_ {
     let (_, seeds) = seeds.split_once(':').expect("seed:");
     seeds
@@ -107,16 +359,17 @@ fn parse_seeds(seeds: &str) -> impl Iterator<Item = i64> + '_ {
 }
 
 fn parse_mapping(s: &str) -> Mapping {
-    let (dst, start, len) = s
-        .trim()
-        .split_ascii_whitespace()
-        .map(|x| x.parse().expect("number"))
-        .collect_tuple()
-        .expect("exactly 3 numbers");
-    Mapping {
-        range: start..start + len,
-        offset: dst - start,
-    }
+    try_parse_mapping(s).expect("exactly 3 numbers")
+}
+
+/// Like [`parse_mapping`], but `None` if the line isn't three numbers describing a valid range.
+fn try_parse_mapping(s: &str) -> Option<Mapping> {
+    let numbers: Option<Vec<i64>> = s.split_ascii_whitespace().map(|x| x.parse().ok()).collect();
+    let (dst, start, len) = numbers?.into_iter().collect_tuple()?;
+    Some(Mapping {
+        range: start..start.checked_add(len)?,
+        offset: dst.checked_sub(start)?,
+    })
 }
 
 fn parse_map(s: &str) -> Map {
@@ -127,9 +380,129 @@ fn parse_map(s: &str) -> Map {
     Map { label, mappings }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Problem {
+    /// The source range overlaps the one of the mapping on the given line.
+    Overlap { line: usize },
+    /// The mapping produces values below zero.
+    NegativeResult,
+    /// The header isn't of the form `a-to-b map:`.
+    BadLabel,
+    /// The line isn't three numbers describing a valid range. It's ignored otherwise.
+    BadMapping,
+    /// No map leads to the map's source category, which isn't the root either.
+    BrokenChain(String),
+    /// No chain of maps leads from the root to the map's source category.
+    UnreachableCategory { category: String, root: String },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Overlap { line } => write!(f, "source range overlaps the one on line {line}"),
+            Problem::NegativeResult => write!(f, "maps to negative values"),
+            Problem::BadLabel => write!(f, "expected a label of the form `a-to-b`"),
+            Problem::BadMapping => write!(f, "expected three numbers: destination, source, length"),
+            Problem::BrokenChain(c) => write!(f, "no map leads to {c}"),
+            Problem::UnreachableCategory { category, root } => {
+                write!(f, "{category} can't be reached from {root}")
+            }
+        }
+    }
+}
+
+/// A problem found by [`validate_almanac`], located by the map's label and a 1-based line number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Diagnostic {
+    label: String,
+    line: usize,
+    problem: Problem,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (line {}): {}", self.label, self.line, self.problem)
+    }
+}
+
+/// Check the assumptions the solvers make about the almanac's maps,
+/// with every map supposed to be reachable from the `root` category.
+fn validate_almanac(input: &str, root: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut report = |label: &str, line, problem| {
+        diagnostics.push(Diagnostic {
+            label: label.to_owned(),
+            line,
+            problem,
+        })
+    };
+
+    // (label, header line, [(line, mapping)])
+    let mut maps = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        if let Some(label) = line.trim().strip_suffix(" map:") {
+            maps.push((label, i + 1, Vec::new()));
+        } else if let Some((label, _, mappings)) = maps.last_mut() {
+            if !line.trim().is_empty() {
+                match try_parse_mapping(line) {
+                    Some(m) => mappings.push((i + 1, m)),
+                    None => report(label, i + 1, Problem::BadMapping),
+                }
+            }
+        }
+    }
+
+    for (label, _, mappings) in &maps {
+        for (j, (line, m)) in mappings.iter().enumerate() {
+            for (other_line, other) in &mappings[..j] {
+                if m.range.start < other.range.end && other.range.start < m.range.end {
+                    report(label, *line, Problem::Overlap { line: *other_line });
+                }
+            }
+            if m.range.start + m.offset < 0 {
+                report(label, *line, Problem::NegativeResult);
+            }
+        }
+    }
+
+    let mut edges = Vec::new();
+    for &(label, line, _) in &maps {
+        match label.split_once("-to-") {
+            Some((src, dst)) => edges.push((label, line, src, dst)),
+            None => report(label, line, Problem::BadLabel),
+        }
+    }
+
+    let mut reachable = vec![root];
+    loop {
+        let before = reachable.len();
+        for &(_, _, src, dst) in &edges {
+            if reachable.contains(&src) && !reachable.contains(&dst) {
+                reachable.push(dst);
+            }
+        }
+        if reachable.len() == before {
+            break;
+        }
+    }
+    for &(label, line, src, _) in &edges {
+        if src != root && edges.iter().all(|e| e.3 != src) {
+            report(label, line, Problem::BrokenChain(src.to_owned()));
+        } else if !reachable.contains(&src) {
+            let problem = Problem::UnreachableCategory {
+                category: src.to_owned(),
+                root: root.to_owned(),
+            };
+            report(label, line, problem);
+        }
+    }
+
+    diagnostics
+}
+
 fn search_sorted_mappings(mappings: &[Mapping], x: i64) -> Option<usize> {
     let mut a = 0;
-    let mut b = mappings.len() - 1;
+    let mut b = mappings.len().checked_sub(1)?;
 
     if x < mappings[a].range.start || x >= mappings[b].range.end {
         return None;
@@ -173,6 +546,207 @@ mod tests {
         assert_eq!(search_sorted_mappings(&ranges, 21), None);
     }
 
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn can_compose_maps() {
+        let (_, rest) = INPUT.split_once("\n\n").unwrap();
+        let maps = rest.split("\n\n").map(parse_map).collect_vec();
+        let almanac = compose_all(&maps);
+        assert_eq!(almanac.label, "seed-to-location");
+        for x in -10..120 {
+            let expected = maps.iter().fold(x, |x, map| map.transform(x));
+            assert_eq!(almanac.transform(x), expected);
+        }
+        assert!(almanac
+            .mappings
+            .windows(2)
+            .all(|w| w[0].range.end <= w[1].range.start));
+
+        let identity = Map::identity();
+        assert_eq!(identity.then(&maps[0]), maps[0]);
+        assert_eq!(maps[0].then(&identity), maps[0]);
+        assert_eq!(identity.transform(42), 42);
+        assert_eq!(identity.transform_range(3..7), [3..7]);
+    }
+
+    #[test]
+    fn can_invert_maps() {
+        let (_, rest) = INPUT.split_once("\n\n").unwrap();
+        let maps = rest.split("\n\n").map(parse_map).collect_vec();
+        let almanac = compose_all(&maps);
+        let inverse = almanac.inverse().unwrap();
+        assert_eq!(inverse.label, "location-to-seed");
+        for x in -10..120 {
+            assert_eq!(inverse.transform(almanac.transform(x)), x);
+        }
+        assert_eq!(almanac.then(&inverse).mappings, []);
+
+        let not_injective = parse_map("a-to-b map:\n0 10 5");
+        assert_eq!(not_injective.inverse(), None);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn can_resolve_category_paths() {
+        let (header, rest) = INPUT.split_once("\n\n").unwrap();
+        let mut blocks = rest.split("\n\n").collect_vec();
+        blocks.reverse();
+        let shuffled = format!("{header}\n\n{}", blocks.join("\n\n"));
+        assert_eq!(solve_part1(&shuffled), Some(35));
+        assert_eq!(solve_part2(&shuffled), Some(46));
+        assert_eq!(validate_almanac(&shuffled, "seed"), []);
+
+        let (seeds, almanac) = parse_almanac(INPUT);
+        let in_order = |from: usize, to: usize, x: i64| {
+            almanac.maps[from..to]
+                .iter()
+                .fold(x, |x, map| map.transform(x))
+        };
+        let humidity = almanac.query("seed", "humidity", &seeds).unwrap();
+        let expected = seeds.iter().map(|&x| in_order(0, 6, x)).collect_vec();
+        assert_eq!(humidity, expected);
+        let location = almanac.query("light", "location", &seeds).unwrap();
+        let expected = seeds.iter().map(|&x| in_order(4, 7, x)).collect_vec();
+        assert_eq!(location, expected);
+
+        let ranges = almanac
+            .query_ranges("light", "location", &[0..100])
+            .unwrap();
+        let mut expected = (0..100).map(|x| in_order(4, 7, x)).collect_vec();
+        expected.sort_unstable();
+        assert_eq!(ranges.into_iter().flatten().collect_vec(), expected);
+
+        assert_eq!(
+            almanac.query("location", "seed", &seeds),
+            Err(QueryError::NoPath {
+                src: "location".to_owned(),
+                dst: "seed".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn can_branch_between_categories() {
+        let almanac = Almanac {
+            maps: vec![
+                parse_map("b-to-d map:\n100 0 10"),
+                parse_map("a-to-b map:\n5 0 5"),
+                parse_map("a-to-c map:\n20 0 10"),
+                parse_map("c-to-e map:\n0 20 5"),
+            ],
+        };
+        assert_eq!(almanac.query("a", "d", &[0, 7]), Ok(vec![105, 107]));
+        assert_eq!(almanac.query("a", "e", &[3, 7]), Ok(vec![3, 27]));
+        assert_eq!(
+            almanac.query_ranges("a", "e", &[0..10]),
+            Ok(vec![0..5, 25..30])
+        );
+        assert!(almanac.resolve("b", "c").is_err());
+    }
+
+    #[test]
+    fn detects_competing_paths() {
+        // a-to-b-to-d and a-to-c-to-d send 1 to 101 and 51 respectively
+        let mut almanac = Almanac {
+            maps: vec![
+                parse_map("a-to-b map:\n10 0 5"),
+                parse_map("b-to-d map:\n100 10 5"),
+                parse_map("a-to-c map:\n20 0 5"),
+                parse_map("c-to-d map:\n50 20 5"),
+            ],
+        };
+        let error = almanac.query("a", "d", &[1]).unwrap_err();
+        assert_eq!(
+            error,
+            QueryError::Ambiguous(vec![
+                vec!["a-to-b".to_owned(), "b-to-d".to_owned()],
+                vec!["a-to-c".to_owned(), "c-to-d".to_owned()],
+            ])
+        );
+        assert_eq!(
+            error.to_string(),
+            "chains disagree: a-to-b, b-to-d / a-to-c, c-to-d"
+        );
+        assert_eq!(almanac.query("a", "b", &[1]), Ok(vec![11]));
+
+        // chains that agree on every value are interchangeable
+        almanac.maps[2] = parse_map("a-to-c map:\n10 0 5");
+        almanac.maps[3] = parse_map("c-to-d map:\n100 10 5");
+        assert_eq!(almanac.query("a", "d", &[1, 7]), Ok(vec![101, 7]));
+
+        // the walk never goes around a cycle, but may pass through one: b-to-d-to-a-to-c
+        almanac.maps.push(parse_map("d-to-a map:\n0 100 5"));
+        assert_eq!(almanac.query("a", "d", &[1]), Ok(vec![101]));
+        assert_eq!(almanac.query("b", "c", &[11]), Ok(vec![11]));
+    }
+
+    #[test]
+    fn can_validate_almanac() {
+        assert_eq!(validate_almanac(INPUT, "seed"), []);
+
+        let input = "\
+seeds: 1 2
+
+seed-to-soil map:
+50 98 2
+0 90 10
+
+fertilizer-to-water map:
+0 0 5
+
+soil map:
+1 2 3
+
+water-to-light map:
+-5 0 10";
+        let diagnostic = |label: &str, line, problem| Diagnostic {
+            label: label.to_owned(),
+            line,
+            problem,
+        };
+        let expected = [
+            diagnostic("seed-to-soil", 5, Problem::Overlap { line: 4 }),
+            diagnostic("water-to-light", 14, Problem::NegativeResult),
+            diagnostic("soil", 10, Problem::BadLabel),
+            diagnostic(
+                "fertilizer-to-water",
+                7,
+                Problem::BrokenChain("fertilizer".to_owned()),
+            ),
+            diagnostic(
+                "water-to-light",
+                13,
+                Problem::UnreachableCategory {
+                    category: "water".to_owned(),
+                    root: "seed".to_owned(),
+                },
+            ),
+        ];
+        assert_eq!(validate_almanac(input, "seed"), expected);
+        assert_eq!(
+            expected[0].to_string(),
+            "seed-to-soil (line 5): source range overlaps the one on line 4"
+        );
+
+        let malformed =
+            "seeds: 1\n\nseed-to-soil map:\n50 98\n0 x 10\n0 9223372036854775807 1\n1 2 3";
+        assert_eq!(
+            validate_almanac(malformed, "seed"),
+            [4, 5, 6].map(|line| diagnostic("seed-to-soil", line, Problem::BadMapping))
+        );
+
+        // the chain may start at any category
+        let planted = INPUT.replace("seed", "plant");
+        assert_eq!(validate_almanac(&planted, "plant"), []);
+        let diagnostics = validate_almanac(&planted, "seed");
+        assert_eq!(diagnostics.len(), 7);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "plant-to-soil (line 3): no map leads to plant"
+        );
+    }
+
     #[test]
     fn can_parse_input() {
         let (header, rest) = INPUT.split_once("\n\n").unwrap();
@@ -184,12 +758,23 @@ mod tests {
 
     #[test]
     fn can_solve_part1() {
-        assert_eq!(solve_part1(INPUT), 35);
+        assert_eq!(solve_part1(INPUT), Some(35));
     }
 
     #[test]
     fn can_solve_part2() {
-        assert_eq!(solve_part2(INPUT), 46);
+        assert_eq!(solve_part2(INPUT), Some(46));
+    }
+
+    #[test]
+    fn handles_missing_seeds() {
+        let (_, maps) = INPUT.split_once('\n').unwrap();
+        let no_seeds = format!("seeds:\n{maps}");
+        assert_eq!(solve_part1(&no_seeds), None);
+        assert_eq!(solve_part2(&no_seeds), None);
+        let empty_ranges = format!("seeds: 79 0 55 0\n{maps}");
+        assert!(solve_part1(&empty_ranges).is_some());
+        assert_eq!(solve_part2(&empty_ranges), None);
     }
 
     const INPUT: &str = "\
@@ -1,41 +1,173 @@
+use num_bigint::BigUint;
+use num_integer::{Integer, Roots};
+use std::fmt::Debug;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
 fn main() {
     let input = std::fs::read_to_string("inputs/day6").unwrap();
-    println!("Part 1: {}", solve_part1(input.trim()));
-    println!("Part 2: {}", solve_part2(input.trim()));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let param = |i: usize| -> f64 { args[i].parse().expect("number") };
+    let model: Box<dyn BoatModel> = match args.first().map(String::as_str) {
+        None => {
+            println!("Part 1: {}", solve_part1(input.trim()));
+            println!("Part 2: {}", solve_part2(input.trim()));
+            return;
+        }
+        Some("--standard") => Box::new(Standard),
+        Some("--capped") => Box::new(Capped {
+            charge_rate: param(1),
+            max_speed: param(2),
+        }),
+        Some("--drag") => Box::new(Drag {
+            charge_rate: param(1),
+            drag: param(2),
+        }),
+        Some(arg) => panic!("unexpected argument: {arg}"),
+    };
+    let (part1, part2) = solve_with(input.trim(), model.as_ref());
+    println!("Part 1: {part1}");
+    println!("Part 2: {part2}");
+}
+
+/// Number of hold times beating the `record` in a race lasting `duration`.
+///
+/// Holding for `t` beats the record iff `t * (duration - t) > record`, which is equivalent to
+/// `(2t - duration)^2 < duration^2 - 4 * record`. So it's enough to count the integers `u = 2t - duration`
+/// of the same parity as `duration` with `|u| <= isqrt(duration^2 - 4 * record - 1)`.
+fn ways_to_win<T: Integer + Roots + Clone>(duration: &T, record: &T) -> T {
+    let two = T::one() + T::one();
+    let four_record = two.clone() * two * record.clone();
+    let square = duration.clone() * duration.clone();
+    if square <= four_record {
+        return T::zero();
+    }
+    let m = (square - four_record - T::one()).sqrt();
+    if (m.clone() + duration.clone()).is_even() {
+        m + T::one()
+    } else {
+        m
+    }
+}
+
+/// How far a boat gets depending on how long the button is held.
+/// The distance must be unimodal in `hold`, i.e. first increasing and then decreasing.
+trait BoatModel {
+    fn distance(&self, hold: u64, duration: u64) -> f64;
+
+    /// All the hold times beating the `record`, found by bisection.
+    /// Models with a closed form solution should override this.
+    fn winning_holds(&self, duration: u64, record: u64) -> Option<RangeInclusive<u64>> {
+        let record = record as f64;
+        let d = |h| self.distance(h, duration);
+        let peak = bisect(0, duration, |h| d(h) >= d(h + 1));
+        if d(peak) <= record {
+            return None;
+        }
+        let first = bisect(0, peak, |h| d(h) > record);
+        let last = bisect(peak, duration + 1, |h| d(h) <= record) - 1;
+        Some(first..=last)
+    }
+}
+
+/// The smallest `x` in `lo..hi` for which `pred` holds, or `hi` if there's none.
+/// `pred` must be false for some prefix of the range and true for the rest.
+fn bisect(mut lo: u64, mut hi: u64, pred: impl Fn(u64) -> bool) -> u64 {
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid) {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    lo
+}
+
+/// The puzzle's boat: every millisecond of holding adds one unit of speed.
+struct Standard;
+
+impl BoatModel for Standard {
+    fn distance(&self, hold: u64, duration: u64) -> f64 {
+        (hold * (duration - hold)) as f64
+    }
+
+    fn winning_holds(&self, duration: u64, record: u64) -> Option<RangeInclusive<u64>> {
+        let ways = ways_to_win(&(duration as u128), &(record as u128)) as u64;
+        // the winning hold times are symmetric around `duration / 2`
+        let first = (duration - ways).div_ceil(2);
+        (ways > 0).then_some(first..=duration - first)
+    }
+}
+
+/// Speed grows by `charge_rate` per millisecond of holding, up to `max_speed`.
+struct Capped {
+    charge_rate: f64,
+    max_speed: f64,
+}
+
+impl BoatModel for Capped {
+    fn distance(&self, hold: u64, duration: u64) -> f64 {
+        let speed = (self.charge_rate * hold as f64).min(self.max_speed);
+        speed * (duration - hold) as f64
+    }
 }
 
-#[inline]
-fn distance(time: usize, race_duration: usize) -> usize {
-    time * (race_duration - time)
+/// Speed grows by `charge_rate` per millisecond of holding,
+/// and once released it decays exponentially, proportionally to the `drag`.
+struct Drag {
+    charge_rate: f64,
+    drag: f64,
+}
+
+impl BoatModel for Drag {
+    fn distance(&self, hold: u64, duration: u64) -> f64 {
+        let speed = self.charge_rate * hold as f64;
+        let time = (duration - hold) as f64;
+        if self.drag == 0.0 {
+            speed * time
+        } else {
+            speed * -(-self.drag * time).exp_m1() / self.drag
+        }
+    }
+}
+
+/// Solve both parts for an arbitrary boat model.
+fn solve_with(input: &str, model: &dyn BoatModel) -> (u64, u64) {
+    let count = |holds: Option<RangeInclusive<u64>>| holds.map_or(0, |r| r.end() - r.start() + 1);
+    let (line1, line2) = input.split_once('\n').unwrap();
+    let durations: Vec<u64> = parse_many("Time:", line1);
+    let records: Vec<u64> = parse_many("Distance:", line2);
+    let part1 = std::iter::zip(durations, records)
+        .map(|(duration, record)| count(model.winning_holds(duration, record)))
+        .product();
+    let duration = parse_single("Time:", line1);
+    let record = parse_single("Distance:", line2);
+    (part1, count(model.winning_holds(duration, record)))
 }
 
 fn solve_part1(input: &str) -> usize {
     let (line1, line2) = input.split_once('\n').unwrap();
-    let durations = parse_many("Time:", line1);
-    let records = parse_many("Distance:", line2);
+    let durations: Vec<usize> = parse_many("Time:", line1);
+    let records: Vec<usize> = parse_many("Distance:", line2);
     assert_eq!(durations.len(), records.len());
 
     std::iter::zip(durations, records)
-        .map(|(duration, record)| {
-            (0..duration)
-                .map(|t| distance(t, duration))
-                .filter(|&d| d > record)
-                .count()
-        })
+        .map(|(duration, record)| ways_to_win(&duration, &record))
         .product()
 }
 
-fn solve_part2(input: &str) -> usize {
+fn solve_part2(input: &str) -> BigUint {
     let (line1, line2) = input.split_once('\n').unwrap();
-    let duration = parse_single("Time:", line1);
+    let duration: BigUint = parse_single("Time:", line1);
     let record = parse_single("Distance:", line2);
-    (0..duration)
-        .map(|t| distance(t, duration))
-        .filter(|&d| d > record)
-        .count()
+    ways_to_win(&duration, &record)
 }
 
-fn parse_many(prefix: &str, line: &str) -> Vec<usize> {
+fn parse_many<T: FromStr>(prefix: &str, line: &str) -> Vec<T>
+where
+    T::Err: Debug,
+{
     line.strip_prefix(prefix)
         .unwrap()
         .split_whitespace()
@@ -43,7 +175,10 @@ fn parse_many(prefix: &str, line: &str) -> Vec<usize> {
         .collect()
 }
 
-fn parse_single(prefix: &str, line: &str) -> usize {
+fn parse_single<T: FromStr>(prefix: &str, line: &str) -> T
+where
+    T::Err: Debug,
+{
     line.strip_prefix(prefix)
         .unwrap()
         .split_whitespace()
@@ -57,6 +192,91 @@ fn parse_single(prefix: &str, line: &str) -> usize {
 mod tests {
     use super::*;
 
+    #[test]
+    fn matches_brute_force() {
+        for duration in 0..60usize {
+            for record in 0..duration * duration / 4 + 2 {
+                let expected = (0..=duration)
+                    .filter(|t| t * (duration - t) > record)
+                    .count();
+                assert_eq!(ways_to_win(&duration, &record), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn can_solve_huge_races() {
+        let duration: BigUint = "123456789012345678901234567890123".parse().unwrap();
+        let record: BigUint = "987654321098765432109876543210".parse().unwrap();
+        let ways = ways_to_win(&duration, &record);
+        // the first and last winning hold times are symmetric around duration / 2
+        let first = (&duration - &ways + 1u32) / 2u32;
+        assert!(&first * (&duration - &first) > record);
+        let before = &first - 1u32;
+        assert!(&before * (&duration - &before) <= record);
+        assert_eq!(
+            ways_to_win(&BigUint::from(30u32), &BigUint::from(200u32)),
+            BigUint::from(9u32)
+        );
+    }
+
+    #[test]
+    fn bisection_matches_closed_form() {
+        struct Bisected;
+        impl BoatModel for Bisected {
+            fn distance(&self, hold: u64, duration: u64) -> f64 {
+                Standard.distance(hold, duration)
+            }
+        }
+        for duration in 0..40 {
+            for record in 0..duration * duration / 4 + 2 {
+                let expected = Standard.winning_holds(duration, record);
+                assert_eq!(Bisected.winning_holds(duration, record), expected);
+                let brute_force = (0..=duration)
+                    .filter(|&h| Standard.distance(h, duration) > record as f64)
+                    .collect::<Vec<_>>();
+                assert_eq!(expected.map_or(vec![], |r| r.collect()), brute_force);
+            }
+        }
+    }
+
+    #[test]
+    fn can_solve_other_models() {
+        assert_eq!(solve_with(INPUT, &Standard), (288, 71503));
+        let no_drag = Drag {
+            charge_rate: 1.0,
+            drag: 0.0,
+        };
+        assert_eq!(solve_with(INPUT, &no_drag), (288, 71503));
+        let uncapped = Capped {
+            charge_rate: 1.0,
+            max_speed: f64::INFINITY,
+        };
+        assert_eq!(solve_with(INPUT, &uncapped), (288, 71503));
+
+        // with a cap of 3 in a 7ms race: 1*6, 2*5, 3*4, 3*3, 3*2, 3*1
+        let capped = Capped {
+            charge_rate: 1.0,
+            max_speed: 3.0,
+        };
+        assert_eq!(capped.winning_holds(7, 8), Some(2..=4));
+        assert_eq!(capped.winning_holds(7, 12), None);
+        let fast = Capped {
+            charge_rate: 2.0,
+            max_speed: 6.0,
+        };
+        assert_eq!(fast.winning_holds(7, 19), Some(2..=3));
+
+        let draggy = Drag {
+            charge_rate: 1.0,
+            drag: 0.5,
+        };
+        let holds = draggy.winning_holds(30, 20).unwrap();
+        for h in 0..=30 {
+            assert_eq!(holds.contains(&h), draggy.distance(h, 30) > 20.0);
+        }
+    }
+
     #[test]
     fn can_solve_part1() {
         assert_eq!(solve_part1(INPUT), 288);
@@ -64,7 +284,7 @@ mod tests {
 
     #[test]
     fn can_solve_part2() {
-        assert_eq!(solve_part2(INPUT), 71503);
+        assert_eq!(solve_part2(INPUT), BigUint::from(71503u32));
     }
 
     const INPUT: &str = "Time:      7  15   30\nDistance:  9  40  200";
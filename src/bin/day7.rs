@@ -1,62 +1,70 @@
-use std::cmp::Ordering;
-
 use itertools::Itertools;
+use std::cmp::Ordering;
+use std::fmt::{self, Display};
 use winnow::{
     ascii::{dec_int, space1},
+    combinator::{eof, repeat, terminated},
     prelude::*,
-    token::any,
+    token::{any, take_till},
 };
 
 fn main() {
     let input = std::fs::read_to_string("inputs/day7").unwrap();
-    let hands = input
-        .trim()
-        .lines()
-        .map(|s| Hand::parser.parse(s).unwrap())
-        .collect_vec();
-    println!("Part 1: {}", solve_part1(hands.clone()));
-    println!("Part 2: {}", solve_part2(hands));
+    let mut args = std::env::args().skip(1).peekable();
+    let rules = if args.next_if_eq("--jokers").is_some() {
+        Rules::part2()
+    } else {
+        Rules::part1()
+    };
+    let parse = |s: &str| rules.parse_cards(s).expect("valid hand");
+    match args.next().as_deref() {
+        Some("--bench") => {
+            benchmark(
+                args.next()
+                    .map_or(1_000_000, |n| n.parse().expect("number")),
+            );
+        }
+        Some("--classify") => {
+            for s in args {
+                let hand_type = rules.hand_type(&parse(&s)).expect("a preset category");
+                println!("{s}: {hand_type:?}");
+            }
+        }
+        Some("--compare") => {
+            let (a, b) = args.collect_tuple().expect("two hands");
+            println!("{}", rules.explain(&parse(&a), &parse(&b)));
+        }
+        Some("--rank") => {
+            let hands = args
+                .map(|s| parse(&s))
+                .sorted_by_cached_key(|h| rules.strength_of(h));
+            for (i, cards) in hands.enumerate() {
+                println!("{}. {}", i + 1, rules.report(&cards));
+            }
+        }
+        Some(arg) => panic!("unexpected argument: {arg}"),
+        None => {
+            println!("Part 1: {}", solve_part1(&input));
+            println!("Part 2: {}", solve_part2(&input));
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-enum Card {
-    N2,
-    N3,
-    N4,
-    N5,
-    N6,
-    N7,
-    N8,
-    N9,
-    T,
-    J,
-    Q,
-    K,
-    A,
-}
-
-impl Card {
-    fn parser(i: &mut &str) -> PResult<Card> {
-        any.verify_map(|c| match c {
-            '2' => Some(Card::N2),
-            '3' => Some(Card::N3),
-            '4' => Some(Card::N4),
-            '5' => Some(Card::N5),
-            '6' => Some(Card::N6),
-            '7' => Some(Card::N7),
-            '8' => Some(Card::N8),
-            '9' => Some(Card::N9),
-            'T' => Some(Card::T),
-            'J' => Some(Card::J),
-            'Q' => Some(Card::Q),
-            'K' => Some(Card::K),
-            'A' => Some(Card::A),
-            _ => None,
-        })
-        .parse_next(i)
-    }
+/// A card, identified by the position of its rank (and suit) in the [`Rules`]' deck.
+/// Cards compare by rank first, so the derived ordering is the tie breaking order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Card {
+    rank: u8,
+    suit: Option<u8>,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Hand {
+    cards: Vec<Card>,
+    bid: i32,
+}
+
+/// The hand categories of the standard game, from the weakest to the strongest.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum HandType {
     HighCard,
@@ -68,69 +76,441 @@ enum HandType {
     FiveOfAKind,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Hand<C> {
-    cards: [C; 5],
-    bid: i32,
+impl HandType {
+    const ALL: [HandType; 7] = [
+        HandType::HighCard,
+        HandType::OnePair,
+        HandType::TwoPair,
+        HandType::ThreeOfAKind,
+        HandType::FullHouse,
+        HandType::FourOfAKind,
+        HandType::FiveOfAKind,
+    ];
+
+    fn category(self) -> Category {
+        match self {
+            HandType::HighCard => Category::new("high card", &[1]),
+            HandType::OnePair => Category::new("one pair", &[2]),
+            HandType::TwoPair => Category::new("two pair", &[2, 2]),
+            HandType::ThreeOfAKind => Category::new("three of a kind", &[3]),
+            HandType::FullHouse => Category::new("full house", &[3, 2]),
+            HandType::FourOfAKind => Category::new("four of a kind", &[4]),
+            HandType::FiveOfAKind => Category::new("five of a kind", &[5]),
+        }
+    }
+}
+
+/// A kind of hand, e.g. a full house is `groups: [3, 2]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Category {
+    name: String,
+    /// Sizes of the groups of same rank cards the hand needs to contain, largest first.
+    groups: Vec<usize>,
+    /// Whether all the cards must also share a suit. Never satisfied by suitless cards.
+    flush: bool,
 }
 
-impl Hand<Card> {
-    #[allow(unused)]
-    fn new(cards: [Card; 5], bid: i32) -> Self {
-        Self { cards, bid }
+impl Category {
+    fn new(name: &str, groups: &[usize]) -> Self {
+        Category {
+            name: name.to_owned(),
+            groups: groups.to_vec(),
+            flush: false,
+        }
+    }
+}
+
+/// The rules of a Camel Cards like game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rules {
+    /// Card ranks from the weakest to the strongest.
+    ranks: Vec<char>,
+    /// Card suits. When non-empty, every card is written as its rank followed by its suit.
+    suits: Vec<char>,
+    hand_size: usize,
+    /// Ranks that act like whichever other rank makes the hand strongest.
+    wild: Vec<char>,
+    /// Hand categories from the weakest to the strongest.
+    /// A hand belongs to the strongest category it can satisfy.
+    categories: Vec<Category>,
+}
+
+impl Rules {
+    fn part1() -> Self {
+        Rules {
+            ranks: "23456789TJQKA".chars().collect(),
+            suits: Vec::new(),
+            hand_size: 5,
+            wild: Vec::new(),
+            categories: HandType::ALL.map(HandType::category).to_vec(),
+        }
+    }
+
+    /// Like part 1, but `J`s are jokers: wild, and the weakest card on their own.
+    fn part2() -> Self {
+        Rules {
+            ranks: "J23456789TQKA".chars().collect(),
+            wild: vec!['J'],
+            ..Rules::part1()
+        }
+    }
+
+    fn card_parser(&self, i: &mut &str) -> PResult<Card> {
+        let rank = any
+            .verify_map(|c| self.ranks.iter().position(|&r| r == c))
+            .parse_next(i)?;
+        let suit = if self.suits.is_empty() {
+            None
+        } else {
+            let suit = any
+                .verify_map(|c| self.suits.iter().position(|&s| s == c))
+                .parse_next(i)?;
+            Some(suit as u8)
+        };
+        Ok(Card {
+            rank: rank as u8,
+            suit,
+        })
     }
-    fn parser(i: &mut &str) -> PResult<Self> {
-        let cp = Card::parser;
-        let cards = (cp, cp, cp, cp, cp).parse_next(i)?.into();
+
+    fn cards_parser(&self, i: &mut &str) -> PResult<Vec<Card>> {
+        let card = |i: &mut &str| self.card_parser(i);
+        take_till(1.., ' ')
+            .and_then(terminated(repeat(self.hand_size, card), eof))
+            .parse_next(i)
+    }
+
+    fn hand_parser(&self, i: &mut &str) -> PResult<Hand> {
+        let cards = self.cards_parser(i)?;
         space1.parse_next(i)?;
         let bid = dec_int.parse_next(i)?;
         Ok(Hand { cards, bid })
     }
-}
 
-fn solve_part1(mut hands: Vec<Hand<Card>>) -> i32 {
-    hands.sort_unstable_by_key(|h| (classify_by(group_cards, h.cards), h.cards));
-    hands
-        .into_iter()
-        .enumerate()
-        .map(|(i, h)| (i as i32 + 1) * h.bid)
-        .sum()
+    fn parse_hand(&self, s: &str) -> Option<Hand> {
+        (|i: &mut &str| self.hand_parser(i)).parse(s).ok()
+    }
+
+    fn parse_cards(&self, s: &str) -> Option<Vec<Card>> {
+        (|i: &mut &str| self.cards_parser(i)).parse(s).ok()
+    }
+
+    fn card_names(&self, cards: &[Card]) -> Vec<String> {
+        let name = |c: &Card| {
+            let rank = self.ranks[c.rank as usize];
+            let suit = c.suit.map(|suit| self.suits[suit as usize]);
+            std::iter::once(rank).chain(suit).collect()
+        };
+        cards.iter().map(name).collect()
+    }
+
+    fn is_wild(&self, card: Card) -> bool {
+        self.wild.contains(&self.ranks[card.rank as usize])
+    }
+
+    /// Index into [`Rules::categories`] of the strongest category the cards satisfy.
+    fn classify(&self, cards: &[Card]) -> usize {
+        let (wild, natural): (Vec<Card>, Vec<Card>) = cards.iter().partition(|c| self.is_wild(**c));
+        let mut counts = natural
+            .iter()
+            .map(|c| c.rank)
+            .sorted_unstable()
+            .dedup_with_count()
+            .map(|(n, _)| n)
+            .collect_vec();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        let same_suit = natural.iter().map(|c| c.suit).all_equal();
+
+        let satisfies = |category: &Category| {
+            // pairing the largest groups with the largest requirements needs the fewest wilds
+            let missing: usize = category
+                .groups
+                .iter()
+                .enumerate()
+                .map(|(i, &n)| n.saturating_sub(counts.get(i).copied().unwrap_or(0)))
+                .sum();
+            missing <= wild.len() && (!category.flush || (!self.suits.is_empty() && same_suit))
+        };
+        self.categories
+            .iter()
+            .rposition(satisfies)
+            .expect("the weakest category should accept every hand")
+    }
+
+    /// The standard category of the cards, if the one they belong to is one of those.
+    fn hand_type(&self, cards: &[Card]) -> Option<HandType> {
+        let category = &self.categories[self.classify(cards)];
+        HandType::ALL
+            .into_iter()
+            .find(|t| t.category() == *category)
+    }
+
+    /// Sort key of a hand, from its category and its cards in order.
+    fn strength(&self, hand: &Hand) -> (usize, Vec<Card>) {
+        self.strength_of(&hand.cards)
+    }
+
+    fn strength_of(&self, cards: &[Card]) -> (usize, Vec<Card>) {
+        (self.classify(cards), cards.to_vec())
+    }
+
+    /// The ranks the wild cards stand in for to reach the given category,
+    /// as `(position in the hand, rank)` pairs.
+    fn substitution(&self, cards: &[Card], category: usize) -> Vec<(usize, char)> {
+        let mut wild = (0..cards.len()).filter(|&i| self.is_wild(cards[i]));
+        // natural ranks, the largest groups first, and the strongest rank among equally large ones
+        let groups = cards
+            .iter()
+            .filter(|c| !self.is_wild(**c))
+            .map(|c| c.rank)
+            .sorted_unstable()
+            .dedup_with_count()
+            .sorted_unstable_by(|a, b| b.cmp(a))
+            .collect_vec();
+        // groups made of wild cards only pretend to be the strongest unused rank
+        let mut spare_ranks = (0..self.ranks.len() as u8)
+            .rev()
+            .filter(|&r| !self.wild.contains(&self.ranks[r as usize]))
+            .filter(|r| groups.iter().all(|g| g.1 != *r));
+
+        let mut result = Vec::new();
+        let mut first_rank = None;
+        for (i, &size) in self.categories[category].groups.iter().enumerate() {
+            let (count, rank) = match groups.get(i) {
+                Some(&(count, rank)) => (count, rank),
+                None => (0, spare_ranks.next().expect("enough ranks")),
+            };
+            first_rank.get_or_insert(rank);
+            for position in wild.by_ref().take(size.saturating_sub(count)) {
+                result.push((position, self.ranks[rank as usize]));
+            }
+        }
+        // spare wild cards might as well join the largest group
+        let rank = first_rank.or(groups.first().map(|g| g.1));
+        if let Some(rank) = rank {
+            result.extend(wild.map(|position| (position, self.ranks[rank as usize])));
+        }
+        result.sort_unstable();
+        result
+    }
+
+    fn report(&self, cards: &[Card]) -> HandReport {
+        let category = self.classify(cards);
+        HandReport {
+            cards: self.card_names(cards),
+            category,
+            name: self.categories[category].name.clone(),
+            substitution: self.substitution(cards, category),
+        }
+    }
+
+    /// Explain how the hands `a` and `b` compare.
+    fn explain(&self, a: &[Card], b: &[Card]) -> Explanation {
+        let hands = [self.report(a), self.report(b)];
+        let tie_break = if hands[0].category == hands[1].category {
+            std::iter::zip(a, b).position(|(x, y)| x.cmp(y).is_ne())
+        } else {
+            None
+        };
+        Explanation {
+            ordering: self.strength_of(a).cmp(&self.strength_of(b)),
+            hands,
+            tie_break,
+        }
+    }
+
+    fn parse_hands(&self, input: &str) -> Vec<Hand> {
+        input
+            .trim()
+            .lines()
+            .map(|s| self.parse_hand(s).expect("valid hand"))
+            .collect()
+    }
+
+    fn total_winnings(&self, hands: &[Hand]) -> i64 {
+        let mut ranked = hands.iter().collect_vec();
+        ranked.sort_by_cached_key(|h| self.strength(h));
+        ranked
+            .into_iter()
+            .enumerate()
+            .map(|(i, h)| (i as i64 + 1) * h.bid as i64)
+            .sum()
+    }
 }
 
-#[repr(transparent)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Jokered<C>(C);
+/// How a single hand was classified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HandReport {
+    cards: Vec<String>,
+    /// Index into [`Rules::categories`]
+    category: usize,
+    name: String,
+    /// The rank each wild card stands in for, by position in the hand
+    substitution: Vec<(usize, char)>,
+}
 
-impl PartialOrd for Jokered<Card> {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+impl Display for HandReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.cards.concat(), self.name)?;
+        if !self.substitution.is_empty() {
+            let subs = self
+                .substitution
+                .iter()
+                .map(|(i, rank)| format!("card {} as {rank}", i + 1));
+            write!(f, " ({})", subs.format(", "))?;
+        }
+        Ok(())
     }
 }
-impl Ord for Jokered<Card> {
-    fn cmp(&self, other: &Self) -> Ordering {
-        match (self.0, other.0) {
-            (Card::J, Card::J) => Ordering::Equal,
-            (Card::J, _) => Ordering::Less,
-            (_, Card::J) => Ordering::Greater,
-            (a, b) => a.cmp(&b),
+
+/// Why one hand beats another, as returned by [`Rules::explain`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Explanation {
+    hands: [HandReport; 2],
+    /// The first position where the cards differ, if the hands are of the same category
+    tie_break: Option<usize>,
+    /// How the first hand compares to the second one
+    ordering: Ordering,
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b] = &self.hands;
+        writeln!(f, "{a}")?;
+        writeln!(f, "{b}")?;
+        let (winner, loser) = match self.ordering {
+            Ordering::Less => (b, a),
+            Ordering::Greater => (a, b),
+            Ordering::Equal => return write!(f, "the hands are equally strong"),
+        };
+        let (w, l) = (winner.cards.concat(), loser.cards.concat());
+        match self.tie_break {
+            None => write!(f, "{w} beats {l}: {} beats {}", winner.name, loser.name),
+            Some(i) => write!(
+                f,
+                "{w} beats {l}: both are {}, and at card {} {} beats {}",
+                winner.name,
+                i + 1,
+                winner.cards[i],
+                loser.cards[i]
+            ),
         }
     }
 }
 
-fn solve_part2(mut hands: Vec<Hand<Card>>) -> i32 {
-    hands.sort_unstable_by_key(|h| {
-        (
-            classify_by(group_with_jokers, h.cards),
-            h.cards.map(Jokered),
-        )
-    });
-    hands
-        .into_iter()
-        .enumerate()
-        .map(|(i, h)| (i as i32 + 1) * h.bid)
-        .sum()
+/// Constant time classifier for suitless hands of up to 5 cards, without flush categories.
+///
+/// With so few cards, the sizes of the groups of natural (non wild) cards are identified by
+/// the number of matching pairs among them, e.g. a full house has 3 + 1 of them.
+/// Together with the number of wild cards that indexes a small table, filled in using [`Rules::classify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HandTable {
+    /// Category by the number of wild cards and the number of matching pairs.
+    categories: [[u8; 11]; 6],
+    /// Whether each rank is wild.
+    wild: Vec<bool>,
+}
+
+impl HandTable {
+    fn new(rules: &Rules) -> Option<Self> {
+        let supported = rules.hand_size <= 5
+            && rules.suits.is_empty()
+            && rules.categories.len() <= u8::MAX as usize
+            && rules.ranks.len() <= u8::MAX as usize
+            && rules.categories.iter().all(|c| !c.flush);
+        if !supported {
+            return None;
+        }
+        let wild = rules
+            .ranks
+            .iter()
+            .map(|r| rules.wild.contains(r))
+            .collect_vec();
+        let mut table = HandTable {
+            categories: [[0; 11]; 6],
+            wild,
+        };
+
+        // a few distinct natural ranks and a wild one are enough to produce every pattern
+        let natural = (0..rules.ranks.len()).filter(|&r| !table.wild[r]);
+        let alphabet = natural
+            .take(rules.hand_size)
+            .chain(table.wild.iter().position(|&w| w))
+            .map(|r| Card {
+                rank: r as u8,
+                suit: None,
+            })
+            .collect_vec();
+        for cards in (0..rules.hand_size)
+            .map(|_| alphabet.iter().copied())
+            .multi_cartesian_product()
+        {
+            let (wild, pairs) = table.pattern(&cards);
+            table.categories[wild][pairs] = rules.classify(&cards) as u8;
+        }
+        Some(table)
+    }
+
+    /// Number of wild cards and of matching pairs among the natural cards.
+    fn pattern(&self, cards: &[Card]) -> (usize, usize) {
+        let mut wild = 0;
+        let mut pairs = 0;
+        for (i, a) in cards.iter().enumerate() {
+            if self.wild[a.rank as usize] {
+                wild += 1;
+                continue;
+            }
+            pairs += cards[i + 1..].iter().filter(|b| b.rank == a.rank).count();
+        }
+        (wild, pairs)
+    }
+
+    /// Same as [`Rules::classify`].
+    fn classify(&self, cards: &[Card]) -> usize {
+        let (wild, pairs) = self.pattern(cards);
+        self.categories[wild][pairs] as usize
+    }
+
+    /// Sort key packing the category and the ranks of the cards, ordered like [`Rules::strength`].
+    fn key(&self, cards: &[Card]) -> u64 {
+        cards.iter().fold(self.classify(cards) as u64, |key, c| {
+            key << 8 | c.rank as u64
+        })
+    }
+
+    /// Rank of every hand, from 1 for the weakest, without allocating per hand.
+    /// Equal hands are ranked in input order, like with the stable sort of [`Rules::total_winnings`].
+    fn ranks(&self, hands: &[Hand]) -> Vec<usize> {
+        let mut keyed = hands
+            .iter()
+            .enumerate()
+            .map(|(i, h)| (self.key(&h.cards), i))
+            .collect_vec();
+        keyed.sort_unstable();
+        let mut ranks = vec![0; hands.len()];
+        for (rank, (_, i)) in keyed.into_iter().enumerate() {
+            ranks[i] = rank + 1;
+        }
+        ranks
+    }
+
+    /// Same as [`Rules::total_winnings`].
+    fn total_winnings(&self, hands: &[Hand]) -> i64 {
+        std::iter::zip(hands, self.ranks(hands))
+            .map(|(h, rank)| rank as i64 * h.bid as i64)
+            .sum()
+    }
 }
 
+/// The joker of the [`Rules::part2`] deck.
+const JOKER: Card = Card {
+    rank: 0,
+    suit: None,
+};
+
+/// Sizes of the groups of same rank cards, smallest first.
+type Grouping = fn([Card; 5]) -> Vec<usize>;
+
 fn group_cards(mut cards: [Card; 5]) -> Vec<usize> {
     cards.sort_unstable();
     let mut counts = cards
@@ -149,7 +529,7 @@ fn group_with_jokers(mut cards: [Card; 5]) -> Vec<usize> {
         .into_iter()
         .dedup_with_count()
         .filter_map(|(n, c)| {
-            if c == Card::J {
+            if c == JOKER {
                 jokers += n;
                 None
             } else {
@@ -166,10 +546,9 @@ fn group_with_jokers(mut cards: [Card; 5]) -> Vec<usize> {
     }
 }
 
-fn classify_by<C, F>(mut group_counts: F, cards: [C; 5]) -> HandType
+fn classify_by<F>(mut group_counts: F, cards: [Card; 5]) -> HandType
 where
-    F: FnMut([C; 5]) -> Vec<usize>,
-    C: Ord,
+    F: FnMut([Card; 5]) -> Vec<usize>,
 {
     let counts = group_counts(cards);
     match &counts[..] {
@@ -184,63 +563,279 @@ where
     }
 }
 
+/// Total winnings of 5 card hands of the presets, sorting by [`classify_by`] like before [`Rules`] existed.
+fn grouped_winnings(group_counts: Grouping, hands: &[Hand]) -> i64 {
+    let mut hands = hands
+        .iter()
+        .map(|h| (<[Card; 5]>::try_from(&h.cards[..]).expect("5 cards"), h.bid))
+        .collect_vec();
+    hands.sort_by_key(|&(cards, _)| (classify_by(group_counts, cards), cards));
+    hands
+        .into_iter()
+        .enumerate()
+        .map(|(i, (_, bid))| (i as i64 + 1) * bid as i64)
+        .sum()
+}
+
+/// Total winnings, using the [`HandTable`] whenever the rules allow it.
+fn solve(rules: &Rules, input: &str) -> i64 {
+    let hands = rules.parse_hands(input);
+    match HandTable::new(rules) {
+        Some(table) => table.total_winnings(&hands),
+        None => rules.total_winnings(&hands),
+    }
+}
+
+fn solve_part1(input: &str) -> i64 {
+    solve(&Rules::part1(), input)
+}
+
+fn solve_part2(input: &str) -> i64 {
+    solve(&Rules::part2(), input)
+}
+
+/// Compare the time it takes to rank `n` random hands by grouping their cards,
+/// with the general classifier, and with the table driven one.
+fn benchmark(n: usize) {
+    use rand::prelude::*;
+    use std::time::Instant;
+
+    let mut rng = StdRng::seed_from_u64(7);
+    let presets: [(&str, Rules, Grouping); 2] = [
+        ("part 1", Rules::part1(), group_cards),
+        ("part 2", Rules::part2(), group_with_jokers),
+    ];
+    for (name, rules, group_counts) in presets {
+        let hands = (0..n)
+            .map(|_| Hand {
+                cards: (0..5)
+                    .map(|_| Card {
+                        rank: rng.gen_range(0..13),
+                        suit: None,
+                    })
+                    .collect(),
+                bid: rng.gen_range(1..1000),
+            })
+            .collect_vec();
+
+        let start = Instant::now();
+        let grouped = grouped_winnings(group_counts, &hands);
+        let grouped_time = start.elapsed();
+
+        let start = Instant::now();
+        let general = rules.total_winnings(&hands);
+        let general_time = start.elapsed();
+
+        let start = Instant::now();
+        let table = HandTable::new(&rules).unwrap();
+        let build_time = start.elapsed();
+
+        let start = Instant::now();
+        let tabled = table.total_winnings(&hands);
+        let table_time = start.elapsed();
+
+        assert_eq!(grouped, general);
+        assert_eq!(general, tabled);
+        println!(
+            "{name}: grouping {grouped_time:?}, general {general_time:?}, \
+             table {table_time:?} plus {build_time:?} to build it ({n} hands)"
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn can_classify() {
-        use Card::*;
+        let rules = Rules::part1();
+        let classify = |s: &str| rules.hand_type(&rules.parse_cards(s).unwrap());
+        assert_eq!(classify("32T3K"), Some(HandType::OnePair));
+        assert_eq!(classify("KK677"), Some(HandType::TwoPair));
+        assert_eq!(classify("KTJJT"), Some(HandType::TwoPair));
+        assert_eq!(classify("T55J5"), Some(HandType::ThreeOfAKind));
+        assert_eq!(classify("KKKKK"), Some(HandType::FiveOfAKind));
+    }
+
+    #[test]
+    fn can_classify_with_jokers() {
+        let rules = Rules::part2();
+        let classify = |s: &str| rules.hand_type(&rules.parse_cards(s).unwrap());
+        assert_eq!(classify("32T3K"), Some(HandType::OnePair));
+        assert_eq!(classify("KTJJT"), Some(HandType::FourOfAKind));
+        assert_eq!(classify("T55J5"), Some(HandType::FourOfAKind));
+        assert_eq!(classify("2233J"), Some(HandType::FullHouse));
+        assert_eq!(classify("JJJJJ"), Some(HandType::FiveOfAKind));
+        assert_eq!(classify("2345J"), Some(HandType::OnePair));
+    }
+
+    #[test]
+    fn can_parse_hand() {
+        let rules = Rules::part1();
+        let card = |rank| Card { rank, suit: None };
         assert_eq!(
-            classify_by(group_cards, [N3, N2, T, N3, K]),
-            HandType::OnePair
+            rules.parse_hand("32T3K 765"),
+            Some(Hand {
+                cards: [1, 0, 8, 1, 11].map(card).to_vec(),
+                bid: 765,
+            })
         );
+        assert_eq!(rules.parse_hand("32T3 765"), None);
+        assert_eq!(rules.parse_hand("32T3KK 765"), None);
+        assert_eq!(rules.parse_hand("32T3X 765"), None);
+    }
+
+    #[test]
+    fn can_play_custom_rules() {
+        // three card hands of suited cards, where a flush beats a pair
+        let rules = Rules {
+            ranks: "A23456789TJQK".chars().collect(),
+            suits: "cdhs".chars().collect(),
+            hand_size: 3,
+            wild: vec!['2'],
+            categories: vec![
+                Category::new("high card", &[1]),
+                Category::new("pair", &[2]),
+                Category {
+                    flush: true,
+                    ..Category::new("flush", &[1])
+                },
+                Category::new("three of a kind", &[3]),
+                Category {
+                    flush: true,
+                    ..Category::new("suited three of a kind", &[3])
+                },
+            ],
+        };
+        let classify = |s: &str| rules.classify(&rules.parse_hand(s).unwrap().cards);
+        assert_eq!(classify("AcKd5h 1"), 0);
+        assert_eq!(classify("AcAd5h 1"), 1);
+        assert_eq!(classify("AcKc5c 1"), 2);
+        assert_eq!(classify("AcAd2h 1"), 3);
+        assert_eq!(classify("Ac2cAc 1"), 4);
+        let cards = |s| rules.parse_cards(s).unwrap();
+        let e = rules.explain(&cards("Ac2dKh"), &cards("Ac2cKc"));
+        assert_eq!(e.hands[0].substitution, [(1, 'K')]);
         assert_eq!(
-            classify_by(group_cards, [K, K, N6, N7, N7]),
-            HandType::TwoPair
+            e.to_string().lines().last(),
+            Some("Ac2cKc beats Ac2dKh: flush beats pair")
         );
-        assert_eq!(classify_by(group_cards, [K, T, J, J, T]), HandType::TwoPair);
         assert_eq!(
-            classify_by(group_cards, [T, N5, N5, J, N5]),
-            HandType::ThreeOfAKind
+            solve(&rules, "AcAd5h 10\nAcKc5c 1\nAcKd5h 100"),
+            100 + 20 + 3
         );
+        assert_eq!(rules.hand_type(&cards("AcKc5c")), None);
+
+        // without suits there's nothing to share, so no hand is a flush
+        let suitless = Rules {
+            suits: Vec::new(),
+            ..rules
+        };
+        let cards = |s| suitless.parse_cards(s).unwrap();
+        assert_eq!(suitless.classify(&cards("AK5")), 0);
+        assert_eq!(suitless.classify(&cards("AA5")), 1);
+        assert_eq!(suitless.classify(&cards("AAA")), 3);
         assert_eq!(
-            classify_by(group_cards, [K, K, K, K, K]),
-            HandType::FiveOfAKind
+            suitless.hand_type(&cards("AAA")),
+            Some(HandType::ThreeOfAKind)
         );
     }
 
     #[test]
-    fn can_parse_hand() {
-        use Card::*;
-        let hands = INPUT
-            .lines()
-            .take(2)
-            .map(|l| Hand::parser.parse(l))
-            .collect_vec();
-        let expected = [
-            Ok(Hand::new([N3, N2, T, N3, K], 765)),
-            Ok(Hand::new([T, N5, N5, J, N5], 684)),
-        ];
-        assert_eq!(hands, expected);
+    fn table_agrees_with_rules() {
+        for rules in [Rules::part1(), Rules::part2()] {
+            let table = HandTable::new(&rules).unwrap();
+            // every hand over a few ranks, including the wild one
+            let ranks = [0, 1, 2, 3, 9, 12];
+            for cards in (0..5)
+                .map(|_| ranks.map(|rank| Card { rank, suit: None }))
+                .multi_cartesian_product()
+            {
+                assert_eq!(table.classify(&cards), rules.classify(&cards), "{cards:?}");
+            }
+            let hands = rules.parse_hands(INPUT);
+            assert_eq!(table.total_winnings(&hands), rules.total_winnings(&hands));
+        }
+        let hands = Rules::part1().parse_hands(INPUT);
+        assert_eq!(grouped_winnings(group_cards, &hands), 6440);
+        let hands = Rules::part2().parse_hands(INPUT);
+        assert_eq!(grouped_winnings(group_with_jokers, &hands), 5905);
+        let suited = Rules {
+            suits: vec!['s'],
+            ..Rules::part1()
+        };
+        assert_eq!(HandTable::new(&suited), None);
+    }
+
+    #[test]
+    fn can_rank_hands() {
+        let rules = Rules::part1();
+        let table = HandTable::new(&rules).unwrap();
+        assert_eq!(table.ranks(&rules.parse_hands(INPUT)), [1, 4, 3, 2, 5]);
+        let rules = Rules::part2();
+        let table = HandTable::new(&rules).unwrap();
+        assert_eq!(table.ranks(&rules.parse_hands(INPUT)), [1, 3, 2, 5, 4]);
+
+        // equal hands keep their order
+        let hands = rules.parse_hands("2345J 1\nKKKKK 2\n2345J 3");
+        assert_eq!(table.ranks(&hands), [1, 3, 2]);
+        assert_eq!(table.ranks(&[]), []);
+    }
+
+    #[test]
+    fn can_explain_comparisons() {
+        let rules = Rules::part2();
+        let explain = |a: &str, b: &str| {
+            rules.explain(
+                &rules.parse_cards(a).unwrap(),
+                &rules.parse_cards(b).unwrap(),
+            )
+        };
+
+        let e = explain("KTJJT", "QQQJA");
+        assert_eq!(e.ordering, Ordering::Greater);
+        assert_eq!(e.tie_break, Some(0));
+        assert_eq!(e.hands[0].name, "four of a kind");
+        assert_eq!(e.hands[0].substitution, [(2, 'T'), (3, 'T')]);
+        assert_eq!(e.hands[1].substitution, [(3, 'Q')]);
+        assert_eq!(
+            e.to_string(),
+            "KTJJT: four of a kind (card 3 as T, card 4 as T)\n\
+             QQQJA: four of a kind (card 4 as Q)\n\
+             KTJJT beats QQQJA: both are four of a kind, and at card 1 K beats Q"
+        );
+
+        let e = explain("32T3K", "2233J");
+        assert_eq!(e.ordering, Ordering::Less);
+        assert_eq!(e.tie_break, None);
+        assert_eq!(e.hands[1].substitution, [(4, '3')]);
+        assert!(e
+            .to_string()
+            .ends_with("2233J beats 32T3K: full house beats one pair"));
+
+        assert_eq!(
+            explain("JJJJJ", "JJJJJ").hands[0].substitution,
+            [(0, 'A'), (1, 'A'), (2, 'A'), (3, 'A'), (4, 'A')]
+        );
+        assert_eq!(explain("JJ2JJ", "22222").hands[0].substitution.len(), 4);
+        assert_eq!(explain("T55J5", "T55J5").ordering, Ordering::Equal);
+
+        // the wild card joins the largest group, or the strongest card if there's none
+        let e = explain("2345J", "2245J");
+        assert_eq!(e.hands[0].substitution, [(4, '5')]);
+        assert_eq!(e.hands[1].substitution, [(4, '2')]);
+        assert_eq!(e.hands[1].name, "three of a kind");
     }
 
     #[test]
     fn can_solve_part1() {
-        let hands = INPUT
-            .lines()
-            .map(|s| Hand::parser.parse(s).unwrap())
-            .collect_vec();
-        assert_eq!(solve_part1(hands), 6440);
+        assert_eq!(solve_part1(INPUT), 6440);
     }
 
     #[test]
     fn can_solve_part2() {
-        let hands = INPUT
-            .lines()
-            .map(|s| Hand::parser.parse(s).unwrap())
-            .collect_vec();
-        assert_eq!(solve_part2(hands), 5905);
+        assert_eq!(solve_part2(INPUT), 5905);
     }
 
     const INPUT: &str = "32T3K 765\nT55J5 684\nKK677 28\nKTJJT 220\nQQQJA 483";
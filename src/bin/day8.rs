@@ -1,5 +1,6 @@
-use aoc2023::lcm;
-use fxhash::FxHashMap;
+use aoc2023::crt;
+use fxhash::{FxHashMap, FxHashSet};
+use std::fmt::{self, Display, Write};
 use winnow::{
     ascii::{alphanumeric1, newline, space0},
     combinator::{delimited, repeat, separated, separated_pair},
@@ -17,26 +18,129 @@ fn main() {
         })
         .unwrap();
 
-    println!("Part 1: {}", solve_part1(&m));
-    println!("Part 2: {}", solve_part2(&m));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let (args, ghosts, findings) = match args[..] {
+        ["--ghosts", ref rest @ ..] => (
+            rest,
+            true,
+            m.diagnose(|n| n.ends_with('A'), |n| n.ends_with('Z')),
+        ),
+        ref rest => (rest, false, m.diagnose(|n| n == "AAA", |n| n == "ZZZ")),
+    };
+    match args {
+        ["--diagnose"] => findings.iter().for_each(|f| println!("{f}")),
+        ["--dot"] => print!("{}", m.to_dot(&findings)),
+        _ if findings
+            .iter()
+            .any(|f| matches!(f, Finding::MissingTarget { .. } | Finding::MissingStart)) =>
+        {
+            findings.iter().for_each(|f| println!("{f}"));
+            std::process::exit(1)
+        }
+        [] => {
+            let show = |steps: Option<usize>| steps.map_or("never".to_string(), |s| s.to_string());
+            for (part, steps) in answers(&Network::new(&m), ghosts) {
+                println!("{part}: {}", show(steps));
+            }
+        }
+        ["--after", start, steps] => {
+            let net = Network::new(&m);
+            let steps = steps.parse().expect("number of steps");
+            match net.ids.get(*start) {
+                Some(&start) => {
+                    println!("{}", net.names[net.position_after(start, steps) as usize])
+                }
+                None => println!("unknown node {start}"),
+            }
+        }
+        _ => panic!("usage: day8 [--ghosts] [--diagnose | --dot | --after START STEPS]"),
+    }
+}
+
+/// The answers of a normal run. Ghosts only walk part 2, as their networks needn't have `AAA` at all.
+fn answers(net: &Network, ghosts: bool) -> Vec<(&'static str, Option<usize>)> {
+    let mut answers = Vec::new();
+    if !ghosts {
+        answers.push(("Part 1", solve_part1(net)));
+    }
+    answers.push(("Part 2", solve_part2(net)));
+    answers
+}
+
+fn solve_part1(net: &Network) -> Option<usize> {
+    net.path_length("AAA", "ZZZ")
 }
 
-fn solve_part1(m: &Model) -> usize {
-    m.path_length("AAA", "ZZZ")
+fn solve_part2(net: &Network) -> Option<usize> {
+    let cycles: Vec<_> = net
+        .names
+        .iter()
+        .filter(|n| n.ends_with('A'))
+        .map(|n| net.cycle(n, |n| n.ends_with('Z')))
+        .collect::<Option<_>>()?;
+    first_meeting(&cycles)
 }
 
-fn solve_part2(m: &Model) -> usize {
-    m.net
-        .keys()
-        .filter(|k| k.ends_with('Z'))
-        .map(|k| m.path_length(k, k))
-        .fold(1, lcm)
+/// The first step at which every ghost stands on an end node at the same time,
+/// or `None` if that never happens.
+fn first_meeting(cycles: &[Cycle]) -> Option<usize> {
+    // before every ghost has entered its cycle there are few enough steps to check them all
+    let settled = cycles.iter().map(|c| c.offset).max()?;
+    if let Some(t) = (0..settled).find(|&t| cycles.iter().all(|c| c.contains(t))) {
+        return Some(t);
+    }
+
+    // afterwards each ghost is on an end node iff the step matches one of its cycle's hits
+    let mut solutions = vec![(0, 1)];
+    for c in cycles {
+        let residues: Vec<_> = c.hits.iter().filter(|&&h| h >= c.offset).collect();
+        solutions = solutions
+            .iter()
+            .flat_map(|&(r, m)| residues.iter().map(move |&&h| (r, m, h)))
+            .filter_map(|(r, m, h)| crt(r, m, h % c.length, c.length))
+            .collect();
+        solutions.sort_unstable();
+        solutions.dedup();
+    }
+    solutions
+        .into_iter()
+        .map(|(r, m)| {
+            if r >= settled {
+                r
+            } else {
+                r + (settled - r).div_ceil(m) * m
+            }
+        })
+        .min()
+}
+
+/// The steps at which a ghost stands on an end node. Its states, the pairs of node and
+/// instruction index, eventually repeat, so these steps are periodic from some point on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cycle {
+    /// The first step of the cycle
+    offset: usize,
+    length: usize,
+    /// The hits up to the end of the first pass through the cycle, in order
+    hits: Vec<usize>,
+}
+
+impl Cycle {
+    fn contains(&self, step: usize) -> bool {
+        let step = if step < self.offset {
+            step
+        } else {
+            self.offset + (step - self.offset) % self.length
+        };
+        self.hits.binary_search(&step).is_ok()
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Dir {
-    Left,
-    Right,
+    Left = 0,
+    Right = 1,
 }
 
 impl Dir {
@@ -56,24 +160,179 @@ struct Model {
     net: FxHashMap<String, (String, String)>,
 }
 
+/// Something about the network that may keep the solvers from finding an answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Finding {
+    /// No node matches the start condition, so there's nothing to walk
+    MissingStart,
+    /// No start node leads to this node
+    Unreachable(String),
+    /// The walk from `start` ends up going around `nodes` forever without meeting an end node
+    DeadCycle {
+        start: String,
+        nodes: Vec<String>,
+    },
+    /// The node leads back to itself in at least one direction
+    SelfLoop(String),
+    MissingTarget {
+        node: String,
+        target: String,
+    },
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::MissingStart => write!(f, "no start nodes"),
+            Finding::Unreachable(n) => write!(f, "{n} can't be reached from any start"),
+            Finding::DeadCycle { start, nodes } => write!(
+                f,
+                "{start} loops forever through {} without reaching an end",
+                nodes.join(", ")
+            ),
+            Finding::SelfLoop(n) => write!(f, "{n} leads to itself"),
+            Finding::MissingTarget { node, target } => {
+                write!(f, "{node} leads to unknown node {target}")
+            }
+        }
+    }
+}
+
 impl Model {
-    fn path_length(&self, start: &str, end: &str) -> usize {
-        let mut i = 0;
-        let mut node = &self.net[start];
-        let mut dirs = self.dirs.iter().cycle();
-
-        loop {
-            let next = match dirs.next().unwrap() {
-                Dir::Left => &node.0,
-                Dir::Right => &node.1,
+    /// Look for problems with walking the network from the nodes matching `is_start`
+    /// to the ones matching `is_end`.
+    fn diagnose(
+        &self,
+        is_start: impl Fn(&str) -> bool,
+        is_end: impl Fn(&str) -> bool,
+    ) -> Vec<Finding> {
+        let mut names: Vec<&String> = self.net.keys().collect();
+        names.sort_unstable();
+        let mut findings = Vec::new();
+        for &node in &names {
+            let (left, right) = &self.net[node];
+            if left == node || right == node {
+                findings.push(Finding::SelfLoop(node.clone()));
+            }
+            for target in [left, right] {
+                if !self.net.contains_key(target) {
+                    findings.push(Finding::MissingTarget {
+                        node: node.clone(),
+                        target: target.clone(),
+                    });
+                }
+            }
+        }
+
+        let starts: Vec<&String> = names.iter().copied().filter(|n| is_start(n)).collect();
+        if starts.is_empty() {
+            findings.push(Finding::MissingStart);
+        }
+        let mut reachable: FxHashSet<&str> = starts.iter().map(|n| n.as_str()).collect();
+        let mut todo: Vec<&str> = reachable.iter().copied().collect();
+        while let Some(node) = todo.pop() {
+            if let Some((left, right)) = self.net.get(node) {
+                for target in [left, right] {
+                    if reachable.insert(target) {
+                        todo.push(target);
+                    }
+                }
+            }
+        }
+        findings.extend(
+            names
+                .iter()
+                .filter(|n| !reachable.contains(n.as_str()))
+                .map(|n| Finding::Unreachable(n.to_string())),
+        );
+
+        for start in starts {
+            if let Some(nodes) = self.dead_cycle(start, &is_end) {
+                findings.push(Finding::DeadCycle {
+                    start: start.clone(),
+                    nodes,
+                });
+            }
+        }
+        findings
+    }
+
+    /// The nodes the walk from `start` eventually cycles through, if none of them is an end node.
+    /// Walks running into a missing node don't count.
+    fn dead_cycle(&self, start: &str, is_end: impl Fn(&str) -> bool) -> Option<Vec<String>> {
+        let mut seen = FxHashMap::default();
+        let mut path = Vec::new();
+        let mut node = start;
+        for step in 0.. {
+            let i = step % self.dirs.len();
+            if let Some(&offset) = seen.get(&(node, i)) {
+                let mut nodes: Vec<&str> = path[offset..].to_vec();
+                if nodes.iter().any(|n| is_end(n)) {
+                    return None;
+                }
+                nodes.sort_unstable();
+                nodes.dedup();
+                return Some(nodes.into_iter().map(str::to_owned).collect());
+            }
+            seen.insert((node, i), step);
+            path.push(node);
+            let (left, right) = self.net.get(node)?;
+            node = match self.dirs[i] {
+                Dir::Left => left,
+                Dir::Right => right,
             };
-            if next == end {
-                return i + 1;
-            } else {
-                node = &self.net[next];
-                i += 1;
+        }
+        unreachable!()
+    }
+
+    /// Render the network in Graphviz DOT, highlighting the `findings`:
+    /// unreachable nodes are greyed out, dead cycles and missing nodes drawn in red.
+    fn to_dot(&self, findings: &[Finding]) -> String {
+        let mut unreachable = FxHashSet::default();
+        let mut dead = FxHashSet::default();
+        let mut missing = FxHashSet::default();
+        for f in findings {
+            match f {
+                Finding::Unreachable(n) => {
+                    unreachable.insert(n.as_str());
+                }
+                Finding::DeadCycle { nodes, .. } => dead.extend(nodes.iter().map(String::as_str)),
+                Finding::MissingTarget { target, .. } => {
+                    missing.insert(target.as_str());
+                }
+                Finding::MissingStart | Finding::SelfLoop(_) => {}
             }
         }
+
+        let mut names: Vec<&String> = self.net.keys().collect();
+        names.sort_unstable();
+        let mut out = String::from("digraph network {\n");
+        for n in &names {
+            let style = if unreachable.contains(n.as_str()) {
+                " [color=gray, fontcolor=gray]"
+            } else if dead.contains(n.as_str()) {
+                " [color=red]"
+            } else {
+                ""
+            };
+            writeln!(out, "    \"{n}\"{style};").unwrap();
+        }
+        let mut missing: Vec<_> = missing.into_iter().collect();
+        missing.sort_unstable();
+        for n in missing {
+            writeln!(
+                out,
+                "    \"{n}\" [color=red, style=dashed, label=\"{n}?\"];"
+            )
+            .unwrap();
+        }
+        for n in &names {
+            let (left, right) = &self.net[*n];
+            writeln!(out, "    \"{n}\" -> \"{left}\" [label=L];").unwrap();
+            writeln!(out, "    \"{n}\" -> \"{right}\" [label=R];").unwrap();
+        }
+        out.push_str("}\n");
+        out
     }
 
     fn parser(i: &mut &str) -> PResult<Self> {
@@ -84,6 +343,105 @@ impl Model {
     }
 }
 
+/// The network with node names interned to dense indices.
+struct Network {
+    names: Vec<String>,
+    ids: FxHashMap<String, u32>,
+    next: Vec<[u32; 2]>,
+    dirs: Vec<Dir>,
+    /// `walks[i][n]` is where the first `i` instructions lead from node `n`
+    walks: Vec<Vec<u32>>,
+    /// `jumps[k][n]` is where `2^k` full passes through the instructions lead from node `n`
+    jumps: Vec<Vec<u32>>,
+}
+
+impl Network {
+    fn new(m: &Model) -> Self {
+        let mut names: Vec<String> = m.net.keys().cloned().collect();
+        names.sort_unstable();
+        let ids: FxHashMap<String, u32> = names
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.clone(), i as u32))
+            .collect();
+        let next: Vec<[u32; 2]> = names
+            .iter()
+            .map(|n| {
+                let (left, right) = &m.net[n];
+                [ids[left], ids[right]]
+            })
+            .collect();
+
+        let mut walks = vec![(0..names.len() as u32).collect::<Vec<_>>()];
+        for dir in &m.dirs {
+            let last = walks.last().unwrap();
+            let walk = last
+                .iter()
+                .map(|&n| next[n as usize][*dir as usize])
+                .collect();
+            walks.push(walk);
+        }
+        let mut jumps = vec![walks.last().unwrap().clone()];
+        while jumps.len() < usize::BITS as usize {
+            let last = jumps.last().unwrap();
+            let jump = last.iter().map(|&n| last[n as usize]).collect();
+            jumps.push(jump);
+        }
+
+        Self {
+            names,
+            ids,
+            next,
+            dirs: m.dirs.clone(),
+            walks,
+            jumps,
+        }
+    }
+
+    fn step(&self, node: u32, step: usize) -> u32 {
+        self.next[node as usize][self.dirs[step % self.dirs.len()] as usize]
+    }
+
+    /// Where `steps` steps lead from `start`, in `O(log steps)`.
+    fn position_after(&self, start: u32, steps: usize) -> u32 {
+        let (passes, rest) = (steps / self.dirs.len(), steps % self.dirs.len());
+        let node = (0..self.jumps.len())
+            .filter(|k| passes >> k & 1 == 1)
+            .fold(start, |n, k| self.jumps[k][n as usize]);
+        self.walks[rest][node as usize]
+    }
+
+    /// Number of steps from `start` to `end`, or `None` if the walk never gets there
+    /// or there's no `start` node.
+    fn path_length(&self, start: &str, end: &str) -> Option<usize> {
+        let c = self.cycle(start, |n| n == end)?;
+        (1..=c.offset + c.length).find(|&step| c.contains(step))
+    }
+
+    /// Follow the instructions from `start` until the state repeats, or `None` if there's no such node.
+    fn cycle(&self, start: &str, is_end: impl Fn(&str) -> bool) -> Option<Cycle> {
+        let mut seen = FxHashMap::default();
+        let mut hits = Vec::new();
+        let mut node = *self.ids.get(start)?;
+        for step in 0.. {
+            let i = step % self.dirs.len();
+            if let Some(&offset) = seen.get(&(node, i)) {
+                return Some(Cycle {
+                    offset,
+                    length: step - offset,
+                    hits,
+                });
+            }
+            seen.insert((node, i), step);
+            if is_end(&self.names[node as usize]) {
+                hits.push(step);
+            }
+            node = self.step(node, i);
+        }
+        unreachable!()
+    }
+}
+
 fn entry_p(i: &mut &str) -> PResult<(String, (String, String))> {
     separated_pair(
         alphanumeric1.map(str::to_owned),
@@ -108,15 +466,143 @@ mod tests {
     #[test]
     fn can_solve_part1() {
         let m = Model::parser.parse(INPUT1).unwrap();
-        assert_eq!(solve_part1(&m), 6);
+        assert_eq!(solve_part1(&Network::new(&m)), Some(6));
     }
 
     #[test]
     fn can_solve_part2() {
         let m = Model::parser.parse(INPUT2).unwrap();
-        assert_eq!(solve_part2(&m), 6);
+        assert_eq!(solve_part2(&Network::new(&m)), Some(6));
+    }
+
+    #[test]
+    fn can_walk_ghost_networks() {
+        // the ghost example has no AAA, which only matters when walking as a human
+        let m = Model::parser.parse(INPUT2).unwrap();
+        let ghosts = m.diagnose(|n| n.ends_with('A'), |n| n.ends_with('Z'));
+        assert!(!ghosts.contains(&Finding::MissingStart));
+        let net = Network::new(&m);
+        assert_eq!(answers(&net, true), [("Part 2", Some(6))]);
+
+        let humans = m.diagnose(|n| n == "AAA", |n| n == "ZZZ");
+        assert!(humans.contains(&Finding::MissingStart));
+        assert_eq!(Finding::MissingStart.to_string(), "no start nodes");
+        assert_eq!(
+            answers(&net, false),
+            [("Part 1", None), ("Part 2", Some(6))]
+        );
+        assert_eq!(net.cycle("AAA", |n| n == "ZZZ"), None);
+    }
+
+    #[test]
+    fn can_solve_irregular_cycles() {
+        // 11A's cycle hits 11Z at steps 3, 4 and then every 5 steps;
+        // 22A only reaches 22Z at step 3 and then every 3 steps
+        let net = Network::new(&Model::parser.parse(INPUT3).unwrap());
+        let c = net.cycle("11A", |n| n.ends_with('Z')).unwrap();
+        assert_eq!(
+            c,
+            Cycle {
+                offset: 2,
+                length: 5,
+                hits: vec![3, 4]
+            }
+        );
+        assert!(c.contains(8) && c.contains(9) && !c.contains(10));
+        // the LCM of the cycle lengths would be 15
+        assert_eq!(solve_part2(&net), Some(3));
+
+        // 9 is the first step that is 1 (mod 4) and 3 (mod 6)
+        let cycles = [
+            Cycle {
+                offset: 0,
+                length: 4,
+                hits: vec![1],
+            },
+            Cycle {
+                offset: 0,
+                length: 6,
+                hits: vec![3],
+            },
+        ];
+        assert_eq!(first_meeting(&cycles), Some(9));
+        // 1 (mod 4) and 2 (mod 6) disagree on parity
+        let cycles = [
+            Cycle {
+                offset: 0,
+                length: 4,
+                hits: vec![1],
+            },
+            Cycle {
+                offset: 0,
+                length: 6,
+                hits: vec![2],
+            },
+        ];
+        assert_eq!(first_meeting(&cycles), None);
+    }
+
+    #[test]
+    fn reports_never() {
+        let m = Model::parser
+            .parse("L\n\n11A = (11Z, 11Z)\n11Z = (11Z, 11Z)\n22A = (22A, 22A)\n22Z = (22Z, 22Z)")
+            .unwrap();
+        assert_eq!(solve_part2(&Network::new(&m)), None);
+    }
+
+    #[test]
+    fn can_jump_ahead() {
+        let net = Network::new(&Model::parser.parse(INPUT2).unwrap());
+        let start = net.ids["22A"];
+        let mut node = start;
+        for steps in 0..50 {
+            assert_eq!(net.position_after(start, steps), node);
+            node = net.step(node, steps);
+        }
+        // 22B, 22C and 22Z repeat forever from step 1 on
+        let far = net.position_after(start, 1_000_000_000_000);
+        assert_eq!(net.names[far as usize], "22B");
+    }
+
+    #[test]
+    fn can_diagnose_networks() {
+        let m = Model::parser.parse(INPUT1).unwrap();
+        let findings = m.diagnose(|n| n == "AAA", |n| n == "ZZZ");
+        assert_eq!(findings, vec![Finding::SelfLoop("ZZZ".into())]);
+
+        let input = "LR\n\nAAA = (BBB, CCC)\nBBB = (AAA, AAA)\nCCC = (DDD, DDD)\nDDD = (DDD, EEE)\nZZZ = (ZZZ, ZZZ)";
+        let m = Model::parser.parse(input).unwrap();
+        let findings = m.diagnose(|n| n == "AAA", |n| n == "ZZZ");
+        assert_eq!(
+            findings,
+            vec![
+                Finding::SelfLoop("DDD".into()),
+                Finding::MissingTarget {
+                    node: "DDD".into(),
+                    target: "EEE".into()
+                },
+                Finding::SelfLoop("ZZZ".into()),
+                Finding::Unreachable("ZZZ".into()),
+                Finding::DeadCycle {
+                    start: "AAA".into(),
+                    nodes: vec!["AAA".into(), "BBB".into()]
+                },
+            ]
+        );
+        // CCC and DDD are reachable through the network, just not by following the instructions
+        let dot = m.to_dot(&findings);
+        assert!(dot.contains("\"AAA\" [color=red];"));
+        assert!(dot.contains("\"ZZZ\" [color=gray, fontcolor=gray];"));
+        assert!(dot.contains("\"EEE\" [color=red, style=dashed, label=\"EEE?\"];"));
+        assert!(dot.contains("\"DDD\" -> \"EEE\" [label=R];"));
+
+        let m = Model::parser
+            .parse(input.replace(" EEE", " DDD").as_str())
+            .unwrap();
+        assert_eq!(solve_part1(&Network::new(&m)), None);
     }
 
     const INPUT1: &str = "LLR\n\nAAA = (BBB, BBB)\nBBB = (AAA, ZZZ)\nZZZ = (ZZZ, ZZZ)";
+    const INPUT3: &str = "L\n\n11A = (11B, 11B)\n11B = (11C, 11C)\n11C = (11Z, 11Z)\n11Z = (12Z, 12Z)\n12Z = (11D, 11D)\n11D = (11E, 11E)\n11E = (11C, 11C)\n22A = (22B, 22B)\n22B = (22C, 22C)\n22C = (22Z, 22Z)\n22Z = (22B, 22B)";
     const INPUT2: &str = "LR\n\n11A = (11B, XXX)\n11B = (XXX, 11Z)\n11Z = (11B, XXX)\n22A = (22B, XXX)\n22B = (22C, 22C)\n22C = (22Z, 22Z)\n22Z = (22B, 22B)\nXXX = (XXX, XXX)";
 }
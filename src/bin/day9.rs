@@ -1,42 +1,303 @@
-use std::iter::successors;
+use std::fmt::{self, Debug, Display};
+use std::io::BufRead;
+use std::str::FromStr;
 
-use itertools::Itertools;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{CheckedAdd, CheckedSub, One, Signed, Zero};
 
 fn main() {
     let input = std::fs::read_to_string("inputs/day9").unwrap();
-    println!("Part 1: {}", solve_part1(input.trim()));
-    println!("Part 2: {}", solve_part2(input.trim()));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let fits = || {
+        input
+            .trim()
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, Polynomial::fit(&parse_line(line))))
+    };
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => {
+            let show = |n: Option<i64>| n.map_or("overflow".to_string(), |n| n.to_string());
+            println!("Part 1: {}", show(solve_part1(input.trim())));
+            println!("Part 2: {}", show(solve_part2(input.trim())));
+        }
+        ["--i128"] => {
+            let show = |n: Option<i128>| n.map_or("overflow".to_string(), |n| n.to_string());
+            println!("Part 1: {}", show(solve_part1(input.trim())));
+            println!("Part 2: {}", show(solve_part2(input.trim())));
+        }
+        ["--big"] => {
+            println!("Part 1: {}", solve_part1::<BigInt>(input.trim()).unwrap());
+            println!("Part 2: {}", solve_part2::<BigInt>(input.trim()).unwrap());
+        }
+        ["--stream"] => {
+            // predict the next value after every sample read from stdin, as soon as its line arrives
+            let mut engine = DifferenceEngine::new();
+            for line in std::io::stdin().lock().lines() {
+                for sample in parse_line::<BigInt>(&line.unwrap()) {
+                    println!("{sample} -> {}", engine.push(sample.clone()).unwrap());
+                }
+            }
+        }
+        ["--fit"] => {
+            for (line, fit) in fits() {
+                match fit {
+                    Ok(p) => match p.degree() {
+                        Some(d) => println!("{line}: degree {d}: {p}"),
+                        None => println!("{line}: {p}"),
+                    },
+                    Err(e) => println!("{line}: {e}"),
+                }
+            }
+        }
+        ["--at", k] => {
+            let k: BigInt = k.parse().expect("an index");
+            let mut total = BigInt::zero();
+            for (line, fit) in fits() {
+                match fit {
+                    Ok(p) => total += p.eval(&k),
+                    Err(e) => {
+                        eprintln!("line {line}: {e}");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            println!("{total}");
+        }
+        _ => panic!("usage: day9 [--i128 | --big | --stream | --fit | --at K]"),
+    }
+}
+
+fn solve_part1<T>(input: &str) -> Option<T>
+where
+    T: Clone + Zero + CheckedAdd + CheckedSub + FromStr,
+    T::Err: Debug,
+{
+    input
+        .lines()
+        .map(|line| extrapolate_forward(&parse_line::<T>(line)))
+        .try_fold(T::zero(), |acc, x| acc.checked_add(&x?))
+}
+
+fn solve_part2<T>(input: &str) -> Option<T>
+where
+    T: Clone + Zero + CheckedAdd + CheckedSub + FromStr,
+    T::Err: Debug,
+{
+    input
+        .lines()
+        .map(|line| extrapolate_back(&parse_line::<T>(line)))
+        .try_fold(T::zero(), |acc, x| acc.checked_add(&x?))
+}
+
+/// The value following `nums`, or `None` on overflow.
+fn extrapolate_forward<T: Clone + Zero + CheckedAdd + CheckedSub>(nums: &[T]) -> Option<T> {
+    let mut engine = DifferenceEngine::new();
+    nums.iter()
+        .try_fold(T::zero(), |_, x| engine.push(x.clone()))
+}
+
+/// The value preceding `nums`, or `None` on overflow.
+fn extrapolate_back<T: Clone + Zero + CheckedAdd + CheckedSub>(nums: &[T]) -> Option<T> {
+    let mut engine = DifferenceEngine::new();
+    for x in nums {
+        engine.push(x.clone())?;
+    }
+    engine.previous()
+}
+
+/// Difference table of a stream of samples, keeping only the diagonals needed to extrapolate
+/// in either direction. Each new sample updates the last diagonal in place.
+#[derive(Debug, Clone)]
+struct DifferenceEngine<T> {
+    /// The first value of each row of differences
+    first: Vec<T>,
+    /// The last value of each row of differences
+    last: Vec<T>,
+    overflowed: bool,
+}
+
+impl<T: Clone + Zero + CheckedAdd + CheckedSub> DifferenceEngine<T> {
+    fn new() -> Self {
+        Self {
+            first: Vec::new(),
+            last: Vec::new(),
+            overflowed: false,
+        }
+    }
+
+    /// Add the next sample and return the prediction for the one after it.
+    /// Once an overflow happened this always returns `None`.
+    fn push(&mut self, sample: T) -> Option<T> {
+        if self.overflowed {
+            return None;
+        }
+        let mut value = sample;
+        for last in &mut self.last {
+            let Some(diff) = value.checked_sub(last) else {
+                self.overflowed = true;
+                return None;
+            };
+            *last = std::mem::replace(&mut value, diff);
+        }
+        // every sample starts a new row, holding a single value so far
+        self.first.push(value.clone());
+        self.last.push(value);
+        self.next()
+    }
+
+    /// The prediction for the next sample.
+    fn next(&self) -> Option<T> {
+        if self.overflowed {
+            return None;
+        }
+        self.last
+            .iter()
+            .try_fold(T::zero(), |acc, x| acc.checked_add(x))
+    }
+
+    /// The prediction for the sample before the first one.
+    fn previous(&self) -> Option<T> {
+        if self.overflowed {
+            return None;
+        }
+        self.first
+            .iter()
+            .try_rfold(T::zero(), |acc, x| x.checked_sub(&acc))
+    }
 }
 
-fn solve_part1(input: &str) -> i32 {
-    input.lines().map(parse_line).map(extrapolate_forward).sum()
+/// The minimal degree polynomial through a sequence sampled at `0, 1, 2, ...`,
+/// in Newton's forward difference form `p(k) = sum(Δ^j p(0) * binomial(k, j))`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Polynomial {
+    /// `Δ^j p(0)` for `j` up to the degree, empty for the zero polynomial
+    differences: Vec<BigInt>,
 }
 
-fn solve_part2(input: &str) -> i32 {
-    input.lines().map(parse_line).map(extrapolate_back).sum()
+/// The differences of a sequence never settled to zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct NotPolynomial {
+    samples: usize,
 }
 
-fn extrapolate_forward(nums: Vec<i32>) -> i32 {
-    successors(Some(nums), |ds| Some(diffs(ds)))
-        .map(|v| v.last().copied())
-        .while_some()
-        .sum()
+impl Display for NotPolynomial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "not a polynomial within {} samples", self.samples)
+    }
 }
 
-fn extrapolate_back(nums: Vec<i32>) -> i32 {
-    successors(Some(nums), |ds| Some(diffs(ds)))
-        .map(|v| v.first().copied())
-        .while_some()
-        .collect_vec() // need a double-ended iterator for rfold
-        .into_iter()
-        .rfold(0, |acc, x| x - acc)
+impl Polynomial {
+    /// Fit the samples exactly. At least one row of the difference table must be all zeros,
+    /// otherwise there's nothing telling the sequence apart from a higher degree one.
+    fn fit(samples: &[i64]) -> Result<Self, NotPolynomial> {
+        let mut row: Vec<BigInt> = samples.iter().map(|&x| BigInt::from(x)).collect();
+        let mut differences = Vec::new();
+        while !row.iter().all(Zero::is_zero) {
+            if row.len() == 1 {
+                return Err(NotPolynomial {
+                    samples: samples.len(),
+                });
+            }
+            differences.push(row[0].clone());
+            row = row.iter().skip(1).zip(&row).map(|(a, b)| a - b).collect();
+        }
+        if samples.is_empty() {
+            return Err(NotPolynomial { samples: 0 });
+        }
+        Ok(Self { differences })
+    }
+
+    /// `None` for the zero polynomial.
+    fn degree(&self) -> Option<usize> {
+        self.differences.len().checked_sub(1)
+    }
+
+    /// The value at any index, including negative ones and ones far past the samples.
+    fn eval(&self, k: &BigInt) -> BigInt {
+        let mut total = BigInt::zero();
+        let mut binomial = BigInt::one();
+        for (j, d) in self.differences.iter().enumerate() {
+            total += d * &binomial;
+            // binomial(k, j + 1) = binomial(k, j) * (k - j) / (j + 1), and the division is exact
+            binomial = binomial * (k - j) / (j + 1);
+        }
+        total
+    }
+
+    /// The coefficients in the monomial basis, lowest degree first, as numerators
+    /// over a common positive denominator, in lowest terms.
+    fn coefficients(&self) -> (Vec<BigInt>, BigInt) {
+        let n = self.differences.len();
+        // d! for the degree d, which every binomial(k, j) divides into
+        let denominator: BigInt = (1..n.max(1)).map(BigInt::from).product();
+        let mut numerators = vec![BigInt::zero(); n];
+        // the falling factorial k (k - 1) ... (k - j + 1), i.e. binomial(k, j) * j!
+        let mut falling = vec![BigInt::one()];
+        let mut factorial = BigInt::one();
+        for (j, d) in self.differences.iter().enumerate() {
+            let scale = d * &denominator / &factorial;
+            for (c, f) in numerators.iter_mut().zip(&falling) {
+                *c += &scale * f;
+            }
+            let mut next = vec![BigInt::zero(); falling.len() + 1];
+            for (i, f) in falling.iter().enumerate() {
+                next[i + 1] += f;
+                next[i] -= f * j;
+            }
+            falling = next;
+            factorial *= j + 1;
+        }
+        let gcd = numerators.iter().fold(denominator.clone(), |g, c| g.gcd(c));
+        let numerators = numerators.into_iter().map(|c| c / &gcd).collect();
+        (numerators, denominator / gcd)
+    }
 }
 
-fn diffs(it: &[i32]) -> Vec<i32> {
-    it.iter().skip(1).zip(it).map(|(a, b)| a - b).collect()
+impl Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (numerators, denominator) = self.coefficients();
+        let mut terms = numerators
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, c)| !c.is_zero())
+            .peekable();
+        if terms.peek().is_none() {
+            return write!(f, "0");
+        }
+        if !denominator.is_one() {
+            write!(f, "(")?;
+        }
+        for (i, (power, c)) in terms.enumerate() {
+            match (i, c.is_negative()) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+            let c = c.abs();
+            if !c.is_one() || power == 0 {
+                write!(f, "{c}")?;
+            }
+            match power {
+                0 => {}
+                1 => write!(f, "k")?,
+                _ => write!(f, "k^{power}")?,
+            }
+        }
+        if !denominator.is_one() {
+            write!(f, ") / {denominator}")?;
+        }
+        Ok(())
+    }
 }
 
-fn parse_line(s: &str) -> Vec<i32> {
+fn parse_line<T: FromStr>(s: &str) -> Vec<T>
+where
+    T::Err: Debug,
+{
     s.split_ascii_whitespace()
         .map(|s| s.parse().unwrap())
         .collect()
@@ -48,15 +309,76 @@ mod tests {
 
     #[test]
     fn can_extrapolate_forward() {
-        assert_eq!(extrapolate_forward(vec![10, 13, 16, 21, 30, 45]), 68);
-        assert_eq!(extrapolate_forward(vec![1, 3, 6, 10, 15, 21]), 28);
-        assert_eq!(extrapolate_forward(vec![0, 3, 6, 9, 12, 15]), 18);
+        assert_eq!(extrapolate_forward(&[10, 13, 16, 21, 30, 45]), Some(68));
+        assert_eq!(extrapolate_forward(&[1, 3, 6, 10, 15, 21]), Some(28));
+        assert_eq!(extrapolate_forward(&[0, 3, 6, 9, 12, 15]), Some(18));
     }
 
     #[test]
     fn can_extrapolate_backward() {
-        assert_eq!(extrapolate_back(vec![10, 13, 16, 21, 30, 45]), 5);
-        assert_eq!(extrapolate_back(vec![1, 3, 6, 10, 15, 21]), 0);
-        assert_eq!(extrapolate_back(vec![0, 3, 6, 9, 12, 15]), -3);
+        assert_eq!(extrapolate_back(&[10, 13, 16, 21, 30, 45]), Some(5));
+        assert_eq!(extrapolate_back(&[1, 3, 6, 10, 15, 21]), Some(0));
+        assert_eq!(extrapolate_back(&[0, 3, 6, 9, 12, 15]), Some(-3));
+    }
+
+    #[test]
+    fn can_stream_samples() {
+        let mut engine = DifferenceEngine::new();
+        let predictions: Vec<_> = [1, 3, 6, 10, 15, 21]
+            .into_iter()
+            .map(|x| engine.push(x).unwrap())
+            .collect();
+        assert_eq!(predictions, [1, 5, 10, 15, 21, 28]);
+        assert_eq!(engine.previous(), Some(0));
+
+        // the differences of (-2)^k are -3 times the sequence itself, so they grow like 3^k
+        let samples: Vec<i64> = (0..45).map(|k| (-2i64).pow(k)).collect();
+        assert_eq!(extrapolate_forward(&samples), None);
+        let big: Vec<BigInt> = samples.iter().map(|&x| x.into()).collect();
+        let expected = extrapolate_forward(&big).unwrap();
+        let wide: Vec<i128> = samples.iter().map(|&x| x.into()).collect();
+        assert_eq!(extrapolate_forward(&wide).map(BigInt::from), Some(expected));
+        assert_eq!(
+            extrapolate_back(&wide).map(BigInt::from),
+            extrapolate_back(&big)
+        );
+
+        let mut engine = DifferenceEngine::new();
+        assert_eq!(engine.push(i64::MAX), Some(i64::MAX));
+        assert_eq!(engine.push(-2), None);
+        assert_eq!(engine.push(0), None);
+        assert_eq!((engine.next(), engine.previous()), (None, None));
+    }
+
+    #[test]
+    fn can_fit_polynomials() {
+        let p = Polynomial::fit(&[1, 3, 6, 10, 15, 21]).unwrap();
+        assert_eq!(p.degree(), Some(2));
+        // the triangular numbers (k + 1)(k + 2) / 2
+        assert_eq!(
+            p.coefficients(),
+            (vec![2.into(), 3.into(), 1.into()], 2.into())
+        );
+        assert_eq!(p.to_string(), "(k^2 + 3k + 2) / 2");
+        assert_eq!(p.eval(&6.into()), 28.into());
+        assert_eq!(p.eval(&(-1).into()), 0.into());
+        let far: BigInt = 1_000_000_000_000i64.into();
+        assert_eq!(p.eval(&far), (&far + 1) * (&far + 2) / 2);
+
+        let p = Polynomial::fit(&[0, 3, 6, 9, 12, 15]).unwrap();
+        assert_eq!(p.to_string(), "3k");
+        assert_eq!(p.eval(&(-1).into()), (-3).into());
+        let p = Polynomial::fit(&[10, 13, 16, 21, 30, 45]).unwrap();
+        assert_eq!(p.eval(&6.into()), 68.into());
+        assert_eq!(p.eval(&(-1).into()), 5.into());
+        assert_eq!(p.to_string(), "(k^3 - 3k^2 + 11k + 30) / 3");
+
+        let p = Polynomial::fit(&[0, 0, 0]).unwrap();
+        assert_eq!((p.degree(), p.to_string()), (None, "0".to_string()));
+        assert_eq!(
+            Polynomial::fit(&[1, 2, 4, 8, 16]),
+            Err(NotPolynomial { samples: 5 })
+        );
+        assert_eq!(Polynomial::fit(&[]), Err(NotPolynomial { samples: 0 }));
     }
 }
@@ -2,6 +2,8 @@ use ndarray::prelude::*;
 use pathfinding::matrix::Matrix;
 use simple_grid::Grid;
 
+pub mod polygon;
+
 /// Read a rectangular matrix of data from a string, where each byte encodes a single value
 /// and rows are separated by newlines. Extra whitespace is allowed both around the grid and between the rows.
 /// # Panics
@@ -93,3 +95,32 @@ pub fn gcd(mut a: usize, mut b: usize) -> usize {
     }
     a
 }
+
+/// Chinese Remainder Theorem for moduli that aren't necessarily coprime.
+/// Returns `(r, lcm(m1, m2))` such that `x ≡ r1 (mod m1)` and `x ≡ r2 (mod m2)`
+/// iff `x ≡ r (mod lcm(m1, m2))`, or `None` if no `x` satisfies both.
+pub fn crt(r1: usize, m1: usize, r2: usize, m2: usize) -> Option<(usize, usize)> {
+    let g = gcd(m1, m2);
+    let diff = r2 as i128 - r1 as i128;
+    if diff % g as i128 != 0 {
+        return None;
+    }
+    // x = r1 + k * m1, where k * (m1 / g) ≡ (r2 - r1) / g (mod m2 / g)
+    let m = (m2 / g) as i128;
+    let k = (diff / g as i128).rem_euclid(m) * mod_inverse((m1 / g) as i128, m) % m;
+    let l = lcm(m1, m2);
+    let x = (r1 as i128 + k * m1 as i128).rem_euclid(l as i128);
+    Some((x as usize, l))
+}
+
+/// Inverse of `a` modulo `m`, assuming they're coprime.
+fn mod_inverse(a: i128, m: i128) -> i128 {
+    let (mut old_r, mut r) = (a.rem_euclid(m), m);
+    let (mut old_s, mut s) = (1, 0);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_s, s) = (s, old_s - q * s);
+    }
+    old_s.rem_euclid(m)
+}
@@ -0,0 +1,130 @@
+//! Polygons with integer vertices, measured with the shoelace formula and Pick's theorem.
+//!
+//! The same polygon answers two kinds of questions about grid puzzles:
+//! * a loop through the centres of cells encloses [`Polygon::interior_points`] cells,
+//! * a trench traced along cells covers [`Polygon::lattice_points`] cells, counting the trench itself.
+//!
+//! All the arithmetic is checked, returning `None` on overflow.
+
+use num_integer::Integer;
+use num_traits::{PrimInt, Signed};
+
+/// Direction of travel along a polygon's boundary, with `x` pointing right and `y` pointing up.
+/// With `y` pointing down, as in grid rows, the two are swapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Clockwise,
+    CounterClockwise,
+    /// The polygon has no area
+    Degenerate,
+}
+
+/// A closed polygon given by its vertices in order. The last vertex connects back to the first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Polygon<T> {
+    vertices: Vec<(T, T)>,
+}
+
+impl<T: PrimInt + Signed + Integer> Polygon<T> {
+    /// Repeating the first vertex at the end is allowed. Collinear vertices are fine too,
+    /// so a path listing every cell works as well as one listing only the corners.
+    pub fn new(mut vertices: Vec<(T, T)>) -> Self {
+        if vertices.len() > 1 && vertices.first() == vertices.last() {
+            vertices.pop();
+        }
+        Self { vertices }
+    }
+
+    pub fn vertices(&self) -> &[(T, T)] {
+        &self.vertices
+    }
+
+    fn edges(&self) -> impl Iterator<Item = ((T, T), (T, T))> + '_ {
+        let next = self.vertices.iter().cycle().skip(1);
+        self.vertices.iter().copied().zip(next.copied())
+    }
+
+    /// Twice the area, positive for counter-clockwise polygons. Always an integer, unlike the area.
+    pub fn double_signed_area(&self) -> Option<T> {
+        self.edges()
+            .try_fold(T::zero(), |acc, ((x0, y0), (x1, y1))| {
+                acc.checked_add(&x0.checked_mul(&y1)?.checked_sub(&x1.checked_mul(&y0)?)?)
+            })
+    }
+
+    /// Twice the unsigned area.
+    pub fn double_area(&self) -> Option<T> {
+        let area = self.double_signed_area()?;
+        (area != T::min_value()).then(|| area.abs())
+    }
+
+    pub fn orientation(&self) -> Option<Orientation> {
+        let area = self.double_signed_area()?;
+        Some(if area.is_positive() {
+            Orientation::CounterClockwise
+        } else if area.is_negative() {
+            Orientation::Clockwise
+        } else {
+            Orientation::Degenerate
+        })
+    }
+
+    /// Number of lattice points on the boundary, vertices included.
+    pub fn boundary_points(&self) -> Option<T> {
+        self.edges()
+            .try_fold(T::zero(), |acc, ((x0, y0), (x1, y1))| {
+                let dx = x1.checked_sub(&x0)?;
+                let dy = y1.checked_sub(&y0)?;
+                acc.checked_add(&dx.gcd(&dy))
+            })
+    }
+
+    /// Number of lattice points strictly inside, from Pick's theorem `A = I + B/2 - 1`.
+    pub fn interior_points(&self) -> Option<T> {
+        let two = T::one() + T::one();
+        let area = self.double_area()?;
+        let inside = area
+            .checked_sub(&self.boundary_points()?)?
+            .checked_add(&two)?;
+        Some(inside / two)
+    }
+
+    /// Number of lattice points inside or on the boundary.
+    pub fn lattice_points(&self) -> Option<T> {
+        self.interior_points()?
+            .checked_add(&self.boundary_points()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_measure_polygons() {
+        // a 2x3 rectangle, clockwise in screen coordinates
+        let p = Polygon::new(vec![(0, 0), (2, 0), (2, 3), (0, 3), (0, 0)]);
+        assert_eq!(p.vertices().len(), 4);
+        assert_eq!(p.double_signed_area(), Some(12));
+        assert_eq!(p.orientation(), Some(Orientation::CounterClockwise));
+        assert_eq!(p.boundary_points(), Some(10));
+        assert_eq!(p.interior_points(), Some(2));
+        assert_eq!(p.lattice_points(), Some(12));
+
+        // a triangle with half-integer area
+        let p = Polygon::new(vec![(0i64, 0), (3, 1), (1, 2)]);
+        assert_eq!(p.double_area(), Some(5));
+        assert_eq!(
+            (p.boundary_points(), p.interior_points()),
+            (Some(3), Some(2))
+        );
+        let reversed = Polygon::new(p.vertices().iter().rev().copied().collect());
+        assert_eq!(reversed.orientation(), Some(Orientation::Clockwise));
+
+        let line = Polygon::new(vec![(0i8, 0), (4, 0)]);
+        assert_eq!(line.orientation(), Some(Orientation::Degenerate));
+        let big = Polygon::new(vec![(0i8, 0), (100, 0), (100, 100), (0, 100)]);
+        assert_eq!(big.double_area(), None);
+        assert_eq!(big.boundary_points(), None);
+    }
+}